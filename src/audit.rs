@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::db::{self, DatabasePtr};
+use crate::model::{Audit, SearchParam};
+use crate::redact::hash;
+
+#[derive(Serialize)]
+pub struct AuditEntry {
+    pub time: String,
+    pub user: String,
+    pub resource: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<String>,
+    // For links, resource is the source of the link and search the search run
+    // on the target resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql: Option<String>,
+    pub params: serde_json::Map<String, Value>,
+    pub rows: usize,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub count_only: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub unmasked: bool,
+}
+
+impl AuditEntry {
+    pub fn new(resource: &str) -> Self {
+        AuditEntry {
+            time: jiff::Timestamp::now().to_string(),
+            user: os_user(),
+            resource: resource.to_owned(),
+            search: None,
+            link: None,
+            target: None,
+            sql: None,
+            params: Default::default(),
+            rows: 0,
+            count_only: false,
+            unmasked: false,
+        }
+    }
+
+    // Records the values of a parameter, hashed if the parameter is sensitive.
+    // Several values are recorded as an array.
+    pub fn add_param(&mut self, name: &str, param: Option<&SearchParam>, values: &[String]) {
+        let sensitive = param.is_some_and(|p| p.sensitive);
+        let value = |v: &String| {
+            if sensitive {
+                Value::String(hash(v))
+            } else {
+                Value::String(v.clone())
+            }
+        };
+
+        let value = match values {
+            [single] => value(single),
+            _ => Value::Array(values.iter().map(value).collect()),
+        };
+        self.params.insert(name.to_owned(), value);
+    }
+}
+
+pub fn os_user() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(passwd_user)
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+// Name of the user running the process in the password database, for when the
+// environment doesn't tell
+#[cfg(target_os = "linux")]
+fn passwd_user() -> Option<String> {
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result = std::ptr::null_mut();
+    let ret = unsafe {
+        libc::getpwuid_r(
+            libc::getuid(),
+            &mut pwd,
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+
+    let name = unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn passwd_user() -> Option<String> {
+    None
+}
+
+pub fn record(audit: &Audit, dbs: &HashMap<String, DatabasePtr>, entry: &AuditEntry) -> Result<()> {
+    if let Some(path) = &audit.file {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("error opening {}", path.display()))?;
+        file.write_all(line.as_bytes())
+            .with_context(|| format!("error writing to {}", path.display()))?;
+    }
+
+    if let Some(connection) = &audit.connection {
+        let db = dbs
+            .get(connection)
+            .with_context(|| format!("not connected to {connection}"))?;
+        db::ensure_connected(db)?;
+        let mut db = db.lock().unwrap();
+        // Unlike queries, inserts are not retried when the connection is
+        // lost, not to record an entry twice
+        let res = db
+            .client()?
+            .execute(
+                &format!("INSERT INTO {} (entry) VALUES ($1)", audit.table),
+                &[&serde_json::to_value(entry)?],
+            )
+            .with_context(|| format!("error inserting into {}", audit.table));
+        if let Err(err) = &res {
+            db.connection_lost(err);
+        }
+        res?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_param() {
+        let param = SearchParam {
+            name: "Email".to_owned(),
+            description: None,
+            ty: None,
+            sensitive: true,
+        };
+
+        let mut entry = AuditEntry::new("user");
+        entry.add_param("ID", None, &["1".to_owned()]);
+        entry.add_param("IDs", None, &["1".to_owned(), "2".to_owned()]);
+        entry.add_param("Email", Some(&param), &["foo@example.com".to_owned()]);
+
+        assert_eq!(entry.params["ID"], "1");
+        assert_eq!(entry.params["IDs"], serde_json::json!(["1", "2"]));
+        assert_eq!(
+            entry.params["Email"],
+            "sha256:321ba197033e81286fedb719d60d4ed5cecaed170733cb4a92013811afc0e3b6"
+        );
+    }
+}
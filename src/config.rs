@@ -0,0 +1,1075 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::model::{Audit, Connection, Resource, Search, validate_resources};
+
+const MERGEABLE_TABLES: [&str; 2] = ["search", "links"];
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub file: PathBuf,
+    pub line: usize,
+    // In characters, starting at 1 like lines
+    pub column: usize,
+    pub span: Range<usize>,
+}
+
+impl Location {
+    pub fn new(file: &Path, text: &str, span: Range<usize>) -> Location {
+        let before = &text[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Location {
+            file: file.to_owned(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            span,
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file.display(), self.line, self.column)
+    }
+}
+
+pub struct Config {
+    pub connections: HashMap<String, Connection>,
+    pub audit: Option<Audit>,
+    pub resources: HashMap<String, Resource>,
+    // Where each resource is defined, keyed by resource id, and where each of
+    // the keys of the configuration is, keyed by dotted path like
+    // "user.links.Blogs.kind" or "user.display.styles[0]"
+    pub locations: HashMap<String, Location>,
+    pub files: Vec<PathBuf>,
+}
+
+impl Config {
+    // Location of a key, or of the closest enclosing key whose location is
+    // known
+    pub fn location(&self, key: &str) -> Option<&Location> {
+        find_location(&self.locations, key)
+    }
+
+    // Connections of the databases holding resources or the audit trail, the
+    // only ones connected to
+    pub fn used_connections(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .resources
+            .values()
+            .map(Resource::connection_name)
+            .chain(self.audit.iter().filter_map(|a| a.connection.as_deref()))
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+fn find_location<'a>(locations: &'a HashMap<String, Location>, key: &str) -> Option<&'a Location> {
+    let mut key = key;
+    loop {
+        if let Some(location) = locations.get(key) {
+            return Some(location);
+        }
+        key = &key[..key.rfind(['.', '['])?];
+    }
+}
+
+#[derive(Default)]
+struct Loader<'a> {
+    // Text of the files being edited, by canonical path, read instead of the
+    // files
+    documents: Option<&'a HashMap<PathBuf, String>>,
+    loaded: HashSet<PathBuf>,
+    connections: toml::Table,
+    audit: Option<Audit>,
+    resources: toml::Table,
+    locations: HashMap<String, Location>,
+    positions: HashMap<String, usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Loads the resources from a TOML file and the files it includes, or from all
+// the TOML files of a directory, and validates them. The configuration is
+// returned if it could be loaded, even if invalid.
+pub fn check_config(
+    path: &Path,
+    documents: Option<&HashMap<PathBuf, String>>,
+) -> (Option<Config>, Vec<Diagnostic>) {
+    let (config, mut diagnostics) = load_config(path, documents);
+    if let Some(config) = &config {
+        diagnostics.extend(validate_resources(config));
+    }
+    diagnostics::sort(&mut diagnostics);
+    (config, diagnostics)
+}
+
+fn load_config(
+    path: &Path,
+    documents: Option<&HashMap<PathBuf, String>>,
+) -> (Option<Config>, Vec<Diagnostic>) {
+    let mut loader = Loader {
+        documents,
+        ..Default::default()
+    };
+
+    if path.is_dir() {
+        match expand_pattern(path, "*.toml") {
+            Ok(files) if files.is_empty() => loader.diagnostics.push(Diagnostic::error(
+                format!("no TOML file found in {}", path.display()),
+                None,
+            )),
+            Ok(files) => {
+                for file in files {
+                    loader.load_file(&file, None);
+                }
+            }
+            Err(err) => loader
+                .diagnostics
+                .push(Diagnostic::error(format!("{err:#}"), None)),
+        }
+    } else {
+        loader.load_file(path, None);
+    }
+
+    let mut connections = HashMap::new();
+    for (name, value) in std::mem::take(&mut loader.connections) {
+        if let Some(connection) = loader.deserialize(&format!("connections.{name}"), value) {
+            connections.insert(name, connection);
+        }
+    }
+
+    let mut resources = HashMap::new();
+    for (resource_id, value) in std::mem::take(&mut loader.resources) {
+        if let Some(mut resource) = loader.deserialize::<Resource>(&resource_id, value) {
+            loader.set_positions(&resource_id, &mut resource);
+            resources.insert(resource_id, resource);
+        }
+    }
+
+    // Validating what could be loaded would report errors about what's missing
+    if !loader.diagnostics.is_empty() {
+        return (None, loader.diagnostics);
+    }
+
+    let mut files: Vec<PathBuf> = loader.loaded.into_iter().collect();
+    files.sort();
+
+    let config = Config {
+        connections,
+        audit: loader.audit,
+        resources,
+        locations: loader.locations,
+        files,
+    };
+    (Some(config), Vec::new())
+}
+
+fn deserialize_at<T: DeserializeOwned>(
+    key: &str,
+    value: toml::Value,
+    locations: &HashMap<String, Location>,
+) -> Result<T, Diagnostic> {
+    serde_path_to_error::deserialize(value).map_err(|err| {
+        let path = err.path().to_string();
+        let key = match path.as_str() {
+            "." => key.to_owned(),
+            _ => format!("{key}.{path}"),
+        };
+        Diagnostic::error(
+            format!("error parsing {key}: {}", err.inner().message()),
+            find_location(locations, &key),
+        )
+    })
+}
+
+fn record_locations(
+    locations: &mut HashMap<String, Location>,
+    file: &Path,
+    text: &str,
+    prefix: &str,
+    table: &DeTable,
+) {
+    for (key, value) in table {
+        let path = match prefix {
+            "" => key.get_ref().to_string(),
+            _ => format!("{prefix}.{}", key.get_ref()),
+        };
+        // Values of tables span several lines, only their key is located
+        let span = match value.get_ref() {
+            DeValue::Table(_) | DeValue::Array(_) => key.span(),
+            _ => key.span().start..value.span().end,
+        };
+        locations.insert(path.clone(), Location::new(file, text, span));
+        record_value_locations(locations, file, text, &path, value);
+    }
+}
+
+fn record_value_locations(
+    locations: &mut HashMap<String, Location>,
+    file: &Path,
+    text: &str,
+    path: &str,
+    value: &Spanned<DeValue>,
+) {
+    match value.get_ref() {
+        DeValue::Table(table) => record_locations(locations, file, text, path, table),
+        DeValue::Array(items) => {
+            for (idx, item) in items.iter().enumerate() {
+                let path = format!("{path}[{idx}]");
+                locations.insert(path.clone(), Location::new(file, text, item.span()));
+                record_value_locations(locations, file, text, &path, item);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Loader<'_> {
+    fn error(&mut self, message: String, location: Option<&Location>) {
+        self.diagnostics.push(Diagnostic::error(message, location));
+    }
+
+    fn deserialize<T: DeserializeOwned>(&mut self, key: &str, value: toml::Value) -> Option<T> {
+        deserialize_at(key, value, &self.locations)
+            .map_err(|diagnostic| self.diagnostics.push(diagnostic))
+            .ok()
+    }
+
+    fn set_positions(&self, resource_id: &str, resource: &mut Resource) {
+        let position = |key: &str| self.positions.get(key).copied().unwrap_or_default();
+        resource.position = position(resource_id);
+        for (name, search) in &mut resource.search {
+            search.position = position(&format!("{resource_id}.search.{name}"));
+        }
+        for (name, link) in &mut resource.links {
+            link.position = position(&format!("{resource_id}.links.{name}"));
+        }
+    }
+
+    fn load_file(&mut self, path: &Path, include: Option<&Location>) {
+        let canonical_path = match path.canonicalize() {
+            Ok(canonical_path) => canonical_path,
+            Err(err) => {
+                return self.error(format!("error opening {}: {err}", path.display()), include);
+            }
+        };
+        if !self.loaded.insert(canonical_path.clone()) {
+            return; // already included by another file
+        }
+
+        let text = match self.documents.and_then(|d| d.get(&canonical_path)) {
+            Some(text) => text.clone(),
+            None => match std::fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(err) => {
+                    return self.error(format!("error opening {}: {err}", path.display()), include);
+                }
+            },
+        };
+
+        let (document, errors) = DeTable::parse_recoverable(&text);
+        if !errors.is_empty() {
+            for err in errors {
+                let span = err.span().unwrap_or_default();
+                let location = Location::new(path, &text, span);
+                self.error(format!("invalid TOML: {}", err.message()), Some(&location));
+            }
+            return;
+        }
+
+        let mut spans = HashMap::new();
+        record_locations(&mut spans, path, &text, "", document.get_ref());
+        let mut values = match toml::Table::deserialize(toml::de::Deserializer::from(document)) {
+            Ok(values) => values,
+            Err(err) => {
+                return self.error(format!("error parsing {}: {err}", path.display()), None);
+            }
+        };
+
+        let includes: Vec<String> = match values.remove("include") {
+            Some(includes) => deserialize_at("include", includes, &spans)
+                .map_err(|diagnostic| self.diagnostics.push(diagnostic))
+                .unwrap_or_default(),
+            None => Vec::new(),
+        };
+
+        match values.remove("connections") {
+            Some(toml::Value::Table(connections)) => {
+                for (name, connection) in connections {
+                    let key = format!("connections.{name}");
+                    if let Some(existing_location) = self.locations.get(&key) {
+                        let message =
+                            format!("connection {name} is already defined in {existing_location}");
+                        self.error(message, spans.get(&key));
+                        continue;
+                    }
+                    self.connections.insert(name, connection);
+                }
+            }
+            Some(_) => self.error(
+                "connections must be a table".to_owned(),
+                spans.get("connections"),
+            ),
+            None => {}
+        }
+
+        let mut audit = values.remove("audit");
+        if audit.is_some()
+            && let Some(existing_location) = self.locations.get("audit")
+        {
+            let message = format!("audit is already defined in {existing_location}");
+            self.error(message, spans.get("audit"));
+            audit = None;
+        }
+
+        let mut resource_ids: Vec<String> = values.keys().cloned().collect();
+        resource_ids.sort_by_key(|id| spans.get(id).map(|location| location.span.start));
+
+        for resource_id in resource_ids {
+            match values.remove(&resource_id) {
+                Some(toml::Value::Table(table)) => self.add_resource(&resource_id, table, &spans),
+                _ => self.error(
+                    format!("{resource_id} is not a resource definition"),
+                    spans.get(&resource_id),
+                ),
+            }
+        }
+
+        let mut keys: Vec<(&String, &Location)> = spans.iter().collect();
+        keys.sort_by_key(|(_, location)| location.span.start);
+        for (key, _) in keys {
+            let position = self.positions.len();
+            self.positions.entry(key.clone()).or_insert(position);
+        }
+
+        // Keys defined in several files are located in the first one
+        for (key, location) in &spans {
+            self.locations
+                .entry(key.clone())
+                .or_insert_with(|| location.clone());
+        }
+
+        if let Some(audit) = audit
+            && let Some(mut audit) = self.deserialize::<Audit>("audit", audit)
+        {
+            // Like includes, the file is relative to the configuration file
+            audit.file = audit
+                .file
+                .map(|file| path.parent().unwrap_or(Path::new(".")).join(file));
+            self.audit = Some(audit);
+        }
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        for (idx, pattern) in includes.iter().enumerate() {
+            let location = spans.get(&format!("include[{idx}]"));
+            match expand_pattern(base, pattern) {
+                Ok(files) if files.is_empty() => self.error(
+                    format!(
+                        "include pattern {pattern} of {} matches no file",
+                        path.display()
+                    ),
+                    location,
+                ),
+                Ok(files) => {
+                    for file in files {
+                        self.load_file(&file, location);
+                    }
+                }
+                Err(err) => self.error(format!("{err:#}"), location),
+            }
+        }
+    }
+
+    fn add_resource(
+        &mut self,
+        resource_id: &str,
+        mut table: toml::Table,
+        spans: &HashMap<String, Location>,
+    ) {
+        for key in MERGEABLE_TABLES {
+            if let Some(toml::Value::Table(entries)) = table.get_mut(key) {
+                entries.retain(|name, _| {
+                    let entry_key = format!("{resource_id}.{key}.{name}");
+                    let Some(existing_location) = self.locations.get(&entry_key) else {
+                        return true;
+                    };
+                    self.diagnostics.push(Diagnostic::error(
+                        format!("{entry_key} is already defined in {existing_location}"),
+                        spans.get(&entry_key),
+                    ));
+                    false
+                });
+            }
+        }
+
+        let Some(toml::Value::Table(existing)) = self.resources.get_mut(resource_id) else {
+            self.resources
+                .insert(resource_id.to_owned(), toml::Value::Table(table));
+            if let Some(location) = spans.get(resource_id) {
+                self.locations
+                    .insert(resource_id.to_owned(), location.clone());
+            }
+            return;
+        };
+
+        for key in MERGEABLE_TABLES {
+            // Other values are reported as duplicates or invalid
+            if !matches!(table.get(key), Some(toml::Value::Table(_))) {
+                continue;
+            }
+            let Some(toml::Value::Table(entries)) = table.remove(key) else {
+                continue;
+            };
+            match existing
+                .entry(key)
+                .or_insert_with(|| toml::Value::Table(Default::default()))
+            {
+                toml::Value::Table(existing_entries) => existing_entries.extend(entries),
+                _ => self.diagnostics.push(Diagnostic::error(
+                    format!("{resource_id}.{key} is not a table"),
+                    find_location(&self.locations, &format!("{resource_id}.{key}")),
+                )),
+            }
+        }
+
+        let mut gives_name = false;
+        for (key, value) in table {
+            let key_path = format!("{resource_id}.{key}");
+            if existing.contains_key(&key) {
+                let existing_location = find_location(&self.locations, &key_path)
+                    .map(|location| format!(" in {location}"))
+                    .unwrap_or_default();
+                let message = match key.as_str() {
+                    "name" => {
+                        format!("resource {resource_id} is already defined{existing_location}")
+                    }
+                    _ => format!("{key_path} is already defined{existing_location}"),
+                };
+                self.diagnostics
+                    .push(Diagnostic::error(message, spans.get(&key_path)));
+                continue;
+            }
+            gives_name |= key == "name";
+            existing.insert(key, value);
+        }
+
+        // The location of a resource is the file giving its name
+        if gives_name && let Some(location) = spans.get(resource_id) {
+            self.locations
+                .insert(resource_id.to_owned(), location.clone());
+        }
+    }
+}
+
+fn toml_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_owned()
+    } else {
+        toml::Value::String(key.to_owned()).to_string()
+    }
+}
+
+pub fn append_search(
+    path: &Path,
+    resource_id: &str,
+    search_id: &str,
+    search: &Search,
+) -> Result<()> {
+    let mut text = format!(
+        "\n[{}.search.{}]\nquery = {}\n",
+        toml_key(resource_id),
+        toml_key(search_id),
+        toml::Value::String(search.query.clone())
+    );
+
+    if !search.params.is_empty() {
+        let params = search
+            .params
+            .iter()
+            .map(|param| {
+                let mut res = format!("{{name = {}", toml::Value::String(param.name.clone()));
+                if let Some(ty) = &param.ty {
+                    write!(&mut res, ", type = {}", toml::Value::String(ty.to_string()))?;
+                }
+                res.push('}');
+                Ok(res)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        writeln!(&mut text, "params = [{}]", params.join(", "))?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("error opening {}", path.display()))?;
+    file.write_all(text.as_bytes())
+        .with_context(|| format!("error writing to {}", path.display()))
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+
+    // Like shells, wildcards don't match hidden files
+    if name.first() == Some(&'.') && pattern.first() != Some(&'.') {
+        return false;
+    }
+
+    matches(&pattern, &name)
+}
+
+// Lists the files matching a path pattern, where * and ? can be used in any
+// component of the path. Relative patterns are relative to base.
+fn expand_pattern(base: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![base.to_owned()];
+
+    for component in Path::new(pattern).components() {
+        let component = component.as_os_str();
+        let Some(component_pattern) = component.to_str().filter(|c| c.contains(['*', '?'])) else {
+            paths = paths.into_iter().map(|p| p.join(component)).collect();
+            continue;
+        };
+
+        let mut matches = Vec::new();
+        for dir in paths.iter().filter(|p| p.is_dir()) {
+            let entries = std::fs::read_dir(dir)
+                .with_context(|| format!("error listing {}", dir.display()))?;
+            for entry in entries {
+                let entry = entry.with_context(|| format!("error listing {}", dir.display()))?;
+                if entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| wildcard_match(component_pattern, name))
+                {
+                    matches.push(entry.path());
+                }
+            }
+        }
+        matches.sort();
+        paths = matches;
+    }
+
+    Ok(paths.into_iter().filter(|p| p.is_file()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_key() {
+        assert_eq!(toml_key("user_id"), "user_id");
+        assert_eq!(toml_key("by email"), "\"by email\"");
+    }
+
+    #[test]
+    fn test_find_location() {
+        let text = "[user.links.Blogs]\nkind = \"blog\"\n";
+        let mut locations = HashMap::new();
+        record_locations(
+            &mut locations,
+            Path::new("users.toml"),
+            text,
+            "",
+            DeTable::parse(text).unwrap().get_ref(),
+        );
+
+        let location = find_location(&locations, "user.links.Blogs.kind").unwrap();
+        assert_eq!((location.line, location.column), (2, 1));
+        assert_eq!(&text[location.span.clone()], "kind = \"blog\"");
+        let location = find_location(&locations, "user.links.Blogs.search_params[0]").unwrap();
+        assert_eq!((location.line, location.column), (1, 13));
+        assert!(find_location(&locations, "post.name").is_none());
+    }
+
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dbdrill-{test}-{}", std::process::id()));
+        for (path, text) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_load_config() {
+        let dir = write_files(
+            "load",
+            &[
+                (
+                    "users.toml",
+                    "include = [\"billing/*.toml\"]\n\
+                     \n\
+                     [connections.billing]\n\
+                     dsn = \"postgres://localhost/billing\"\n\
+                     \n\
+                     [user]\n\
+                     name = \"User\"\n\
+                     \n\
+                     [user.search.id]\n\
+                     query = \"SELECT * FROM users WHERE id = $1\"\n\
+                     params = [{name = \"ID\", type = \"integer\"}]\n",
+                ),
+                (
+                    "posts.toml",
+                    "[post]\n\
+                     name = \"Post\"\n\
+                     \n\
+                     [post.search.author]\n\
+                     query = \"SELECT * FROM posts WHERE author_id = $1\"\n\
+                     params = [{name = \"Author ID\", type = \"integer\"}]\n\
+                     \n\
+                     [user.links.Posts]\n\
+                     kind = \"post\"\n\
+                     search = \"author\"\n\
+                     search_params = [\"id\"]\n",
+                ),
+                (
+                    "billing/invoices.toml",
+                    "[invoice]\n\
+                     name = \"Invoice\"\n\
+                     connection = \"billing\"\n\
+                     \n\
+                     [invoice.search.customer]\n\
+                     query = \"SELECT * FROM invoices WHERE customer_id = $1\"\n\
+                     params = [{name = \"Customer ID\", type = \"integer\"}]\n\
+                     \n\
+                     [user.links.Invoices]\n\
+                     kind = \"invoice\"\n\
+                     search = \"customer\"\n\
+                     search_params = [\"id\"]\n",
+                ),
+                // Only the files matching the include pattern are loaded
+                ("billing/notes.txt", "not TOML"),
+                ("billing/old/invoices.toml", "[invoice]\nname = \"Old\"\n"),
+            ],
+        );
+
+        let (config, diagnostics) = check_config(&dir, None);
+        let config = config.unwrap();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        assert_eq!(
+            config.files,
+            [
+                dir.join("billing/invoices.toml"),
+                dir.join("posts.toml"),
+                dir.join("users.toml")
+            ]
+        );
+        let mut resource_ids: Vec<&String> = config.resources.keys().collect();
+        resource_ids.sort();
+        assert_eq!(resource_ids, ["invoice", "post", "user"]);
+        assert!(config.connections.contains_key("billing"));
+
+        let user = &config.resources["user"];
+        let mut links: Vec<&String> = user.links.keys().collect();
+        links.sort();
+        assert_eq!(links, ["Invoices", "Posts"]);
+        let location = config.location("user.links.Invoices.kind").unwrap();
+        assert_eq!(location.file, dir.join("billing/invoices.toml"));
+        assert_eq!(location.line, 10);
+        assert_eq!(
+            config.location("user").unwrap().file,
+            dir.join("users.toml")
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_connection_routing() {
+        let dir = write_files(
+            "routing",
+            &[(
+                "dbdrill.toml",
+                "[connections.billing]\n\
+                 dsn_env = \"CARGO_PKG_NAME\"\n\
+                 \n\
+                 [connections.analytics]\n\
+                 dsn_env = \"DBDRILL_TEST_UNSET\"\n\
+                 \n\
+                 [connections.audit]\n\
+                 dsn = \"postgres://localhost/audit\"\n\
+                 \n\
+                 [audit]\n\
+                 connection = \"audit\"\n\
+                 \n\
+                 [user]\n\
+                 name = \"User\"\n\
+                 \n\
+                 [user.search.id]\n\
+                 query = \"SELECT * FROM users WHERE id = $1\"\n\
+                 params = [{name = \"ID\", type = \"integer\"}]\n\
+                 \n\
+                 [user.links.Invoices]\n\
+                 kind = \"invoice\"\n\
+                 search = \"customer\"\n\
+                 search_params = [\"id\"]\n\
+                 \n\
+                 [invoice]\n\
+                 name = \"Invoice\"\n\
+                 connection = \"billing\"\n\
+                 \n\
+                 [invoice.search.customer]\n\
+                 query = \"SELECT * FROM invoices WHERE customer_id = $1\"\n\
+                 params = [{name = \"Customer ID\", type = \"integer\"}]\n",
+            )],
+        );
+        let (config, diagnostics) = check_config(&dir, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let config = config.unwrap();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+        // Links run their search on the connection of their target
+        let user = &config.resources["user"];
+        assert_eq!(user.connection_name(), crate::model::DEFAULT_CONNECTION);
+        let target = &config.resources[&user.links["Invoices"].kind];
+        assert_eq!(target.connection_name(), "billing");
+
+        // Unused connections are not connected to
+        assert_eq!(config.used_connections(), ["audit", "billing", "default"]);
+
+        // Set by cargo when running the tests
+        assert_eq!(
+            config.connections["billing"].dsn().unwrap().as_deref(),
+            Some("dbdrill")
+        );
+        assert!(config.connections["analytics"].dsn().is_err());
+    }
+
+    #[test]
+    fn test_load_config_duplicates() {
+        let definitions = |n: u32| {
+            format!(
+                "[connections.main]\n\
+                 dsn = \"postgres://localhost/db{n}\"\n\
+                 \n\
+                 [audit]\n\
+                 file = \"audit{n}.jsonl\"\n\
+                 \n\
+                 [user]\n\
+                 name = \"User {n}\"\n\
+                 \n\
+                 [user.search.id]\n\
+                 query = \"SELECT {n}\"\n"
+            )
+        };
+        let dir = write_files(
+            "duplicates",
+            &[("a.toml", &definitions(1)), ("b.toml", &definitions(2))],
+        );
+
+        let (config, diagnostics) = check_config(&dir, None);
+        assert!(config.is_none());
+        let errors: Vec<(&str, usize)> = diagnostics
+            .iter()
+            .map(|d| {
+                let location = d.location.as_ref().unwrap();
+                assert_eq!(location.file, dir.join("b.toml"));
+                (d.message.as_str(), location.line)
+            })
+            .collect();
+        let a = dir.join("a.toml");
+        let a = a.display();
+        assert_eq!(
+            errors,
+            [
+                (
+                    format!("connection main is already defined in {a}:1:14").as_str(),
+                    1
+                ),
+                (format!("audit is already defined in {a}:4:2").as_str(), 4),
+                (
+                    format!("resource user is already defined in {a}:8:1").as_str(),
+                    8
+                ),
+                (
+                    format!("user.search.id is already defined in {a}:10:14").as_str(),
+                    10
+                ),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_append_search() {
+        let dir = write_files(
+            "append",
+            &[
+                ("users.toml", "[user]\nname = \"User\"\n"),
+                (
+                    "posts.toml",
+                    "[post]\n\
+                     name = \"Post\"\n\
+                     \n\
+                     [post.search.author]\n\
+                     query = \"SELECT * FROM posts WHERE author_id = $1\"\n\
+                     params = [{name = \"Author ID\", type = \"integer\"}]\n\
+                     \n\
+                     [user.links.Posts]\n\
+                     kind = \"post\"\n\
+                     search = \"author\"\n\
+                     search_params = [\"id\"]\n",
+                ),
+            ],
+        );
+        let posts = std::fs::read_to_string(dir.join("posts.toml")).unwrap();
+
+        let (config, _) = check_config(&dir, None);
+        let file = &config.unwrap().locations["user"].file;
+        let search: Search = toml::from_str(
+            "query = \"SELECT * FROM users\\nWHERE email = $1 AND name <> '\\\"'\"\n\
+             params = [{name = \"Email \\\"primary\\\"\", type = \"text\"}]",
+        )
+        .unwrap();
+        append_search(file, "user", "by email", &search).unwrap();
+
+        let (config, diagnostics) = check_config(&dir, None);
+        let config = config.unwrap();
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        let appended = &config.resources["user"].search["by email"];
+        assert_eq!(appended.query, search.query);
+        assert_eq!(appended.params[0].name, "Email \"primary\"");
+        assert_eq!(
+            appended.params[0].ty.as_ref().map(ToString::to_string),
+            Some("text".to_owned())
+        );
+        assert_eq!(
+            config.location("user.search.by email").unwrap().file,
+            dir.join("users.toml")
+        );
+        assert!(
+            std::fs::read_to_string(dir.join("users.toml"))
+                .unwrap()
+                .contains("[user.search.\"by email\"]")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("posts.toml")).unwrap(),
+            posts
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn check_files(test: &str, files: &[(&str, &str)]) -> Vec<(String, usize, String)> {
+        let dir = write_files(test, files);
+        let (_, diagnostics) = check_config(&dir, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        diagnostics
+            .into_iter()
+            .map(|d| {
+                let location = d.location.unwrap();
+                let file = location.file.file_name().unwrap().to_string_lossy();
+                let message = d.message.replace(&format!("{}/", dir.display()), "");
+                (file.into_owned(), location.line, message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check_config_errors() {
+        let files = [
+            (
+                "users.toml",
+                "[user]\n\
+                 name = \"User\"\n\
+                 connection = \"missing\"\n\
+                 title = \"{name\"\n\
+                 \n\
+                 [user.search.id]\n\
+                 query = \"SELECT * FROM users WHERE id = $1\"\n\
+                 params = [{name = \"ID\", type = \"integer\"}]\n\
+                 shortcut = \"i\"\n\
+                 \n\
+                 [user.search.email]\n\
+                 query = \"SELECT * FROM users WHERE email = $1\"\n\
+                 params = [{name = \"Email\"}]\n\
+                 shortcut = \"i\"\n\
+                 \n\
+                 [user.search.name]\n\
+                 query = \"SELECT * FROM users WHERE name = $1\"\n\
+                 params = [{name = \"Name\"}]\n\
+                 shortcut = \"?\"\n\
+                 \n\
+                 [user.search.query]\n\
+                 query = \"SELECT * FROM users WHERE name = $1\"\n\
+                 params = [{name = \"Name\"}]\n\
+                 shortcut = \"q\"\n\
+                 \n\
+                 [user.search.back]\n\
+                 query = \"SELECT * FROM users WHERE name = $1\"\n\
+                 params = [{name = \"Name\"}]\n\
+                 shortcut = \"\\u001b\"\n",
+            ),
+            (
+                "posts.toml",
+                "[post]\n\
+                 name = \"User\"\n\
+                 \n\
+                 [post.search.id]\n\
+                 query = \"SELECT * FROM posts WHERE id = $1\"\n\
+                 params = [{name = \"ID\", type = \"integer\"}]\n\
+                 \n\
+                 [[post.display.styles]]\n\
+                 style = \"blinking\"\n\
+                 \n\
+                 [post.links.Comments]\n\
+                 kind = \"comment\"\n\
+                 search = \"post\"\n\
+                 search_params = [\"id\"]\n",
+            ),
+        ];
+
+        // All the errors are reported at once, in the same order every time
+        let errors = check_files("errors", &files);
+        let expected: Vec<(String, usize, String)> = [
+            ("posts.toml", 9, "invalid style blinking"),
+            (
+                "posts.toml",
+                12,
+                "link Comments references a non existing resource comment",
+            ),
+            (
+                "users.toml",
+                2,
+                "resource user has the same name as post (posts.toml:1:2)",
+            ),
+            (
+                "users.toml",
+                3,
+                "resource user uses an unknown connection missing",
+            ),
+            (
+                "users.toml",
+                4,
+                "invalid title: unterminated placeholder in template \"{name\"",
+            ),
+            (
+                "users.toml",
+                9,
+                "shortcut i is already used by user.search.email",
+            ),
+            ("users.toml", 19, "shortcut ? is reserved by the pickers"),
+            ("users.toml", 24, "shortcut q is reserved by the pickers"),
+            (
+                "users.toml",
+                29,
+                "shortcut \\u{1b} is reserved by the pickers",
+            ),
+        ]
+        .into_iter()
+        .map(|(file, line, message)| (file.to_owned(), line, message.to_owned()))
+        .collect();
+        assert_eq!(errors, expected);
+        for _ in 0..3 {
+            assert_eq!(check_files("errors", &files), errors);
+        }
+    }
+
+    #[test]
+    fn test_check_config_warnings() {
+        let warnings = check_files(
+            "warnings",
+            &[(
+                "dbdrill.toml",
+                "[user]\n\
+                 name = \"User\"\n\
+                 \n\
+                 [user.search.id]\n\
+                 query = \"SELECT * FROM users WHERE id = $1\"\n\
+                 params = [{name = \"ID\", type = \"integer\"}]\n\
+                 \n\
+                 [user.links.Posts]\n\
+                 kind = \"post\"\n\
+                 search = \"author\"\n\
+                 search_params = [\"id\"]\n\
+                 \n\
+                 [user.links.Writings]\n\
+                 kind = \"post\"\n\
+                 search = \"author\"\n\
+                 search_params = [\"id\"]\n\
+                 \n\
+                 [user.links.Drafts]\n\
+                 kind = \"post\"\n\
+                 search = \"author\"\n\
+                 search_params = [\"id\"]\n\
+                 if = {eq = [\"status\", \"draft\"]}\n\
+                 \n\
+                 [post]\n\
+                 name = \"Post\"\n\
+                 \n\
+                 [post.search.author]\n\
+                 query = \"SELECT * FROM posts WHERE author_id = $1\"\n\
+                 params = [{name = \"Author ID\", type = \"integer\"}]\n\
+                 \n\
+                 [post.search.ids]\n\
+                 query = \"SELECT * FROM posts WHERE id = ANY($1)\"\n\
+                 params = [{name = \"IDs\", type = \"integer[]\"}]\n\
+                 \n\
+                 [tag]\n\
+                 name = \"Tag\"\n",
+            )],
+        );
+
+        let messages: Vec<(usize, &str)> = warnings
+            .iter()
+            .map(|(_, line, message)| (*line, message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    13,
+                    "link Writings shows the same rows as link Posts, with the same search and parameters"
+                ),
+                (
+                    31,
+                    "search ids only has array parameters but no link uses it"
+                ),
+                (
+                    35,
+                    "resource tag has no search and no link points to it, so it can't be displayed"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*.toml", "billing.toml"));
+        assert!(wildcard_match("user?.toml", "users.toml"));
+        assert!(!wildcard_match("*.toml", "billing.toml.bak"));
+        assert!(!wildcard_match("*.toml", ".hidden.toml"));
+        assert!(wildcard_match(".*.toml", ".hidden.toml"));
+    }
+}
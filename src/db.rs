@@ -0,0 +1,271 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use crate::pgconfig::{self, TlsConfig, Verify};
+
+const RECONNECT_DELAYS: [Duration; 4] = [
+    Duration::ZERO,
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(2),
+];
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Database {
+    name: String,
+    // Includes the password prompted for, if any, to reconnect without asking
+    config: postgres::Config,
+    tls: TlsConfig,
+    client: Option<postgres::Client>,
+    error: Option<String>,
+}
+
+pub type DatabasePtr = Arc<Mutex<Database>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Disconnected,
+    Unreachable,
+}
+
+// Like libpq, the certificate of the server is only checked by the verify-ca
+// and verify-full SSL modes
+pub fn tls_connector(tls: &TlsConfig) -> Result<postgres_native_tls::MakeTlsConnector> {
+    let mut builder = native_tls::TlsConnector::builder();
+    match tls.verify {
+        Verify::None => builder.danger_accept_invalid_certs(true),
+        Verify::Ca => builder.danger_accept_invalid_hostnames(true),
+        Verify::Full => &mut builder,
+    };
+    if let Some(path) = &tls.root_cert {
+        for cert in read_certificates(path)
+            .with_context(|| format!("error reading the certificates of {}", path.display()))?
+        {
+            builder.add_root_certificate(cert);
+        }
+    }
+
+    let connector = builder.build().context("error setting up TLS")?;
+    Ok(postgres_native_tls::MakeTlsConnector::new(connector))
+}
+
+fn read_certificates(path: &std::path::Path) -> Result<Vec<native_tls::Certificate>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+
+    let text = std::fs::read_to_string(path)?;
+    let certs: Vec<_> = text
+        .match_indices(BEGIN)
+        .map(|(start, _)| {
+            let end = text[start + BEGIN.len()..]
+                .find(BEGIN)
+                .map_or(text.len(), |end| start + BEGIN.len() + end);
+            native_tls::Certificate::from_pem(&text.as_bytes()[start..end])
+        })
+        .collect::<Result<_, _>>()?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificate found");
+    }
+
+    Ok(certs)
+}
+
+// Connects to a database, prompting for the password if the server wants one
+// that wasn't found, like psql does
+pub fn connect(name: &str, mut config: postgres::Config, tls: TlsConfig) -> Result<Database> {
+    let client = match config.connect(tls_connector(&tls)?) {
+        Err(err) if asks_for_password(&config, &err) => {
+            let password = pgconfig::prompt_password(&format!(
+                "Password for {} ({name}): ",
+                config.get_user().unwrap_or_default()
+            ))?;
+            config.password(password);
+            config.connect(tls_connector(&tls)?)
+        }
+        res => res,
+    }
+    .context("error connecting to DB")?;
+
+    Ok(Database {
+        name: name.to_owned(),
+        config,
+        tls,
+        client: Some(client),
+        error: None,
+    })
+}
+
+// Whether connecting failed because the server asked for a password that
+// wasn't given. tokio-postgres reports it as a configuration error, whose kind
+// isn't public: it's told apart from the errors coming from the server, the
+// network and TLS, which have their own causes.
+fn asks_for_password(config: &postgres::Config, err: &postgres::Error) -> bool {
+    config.get_password().is_none()
+        && err.as_db_error().is_none()
+        && !err.is_closed()
+        && std::error::Error::source(err)
+            .is_some_and(|cause| !cause.is::<std::io::Error>() && !cause.is::<native_tls::Error>())
+}
+
+impl Database {
+    pub fn client(&mut self) -> Result<&mut postgres::Client> {
+        let name = &self.name;
+        self.client
+            .as_mut()
+            .filter(|client| !client.is_closed())
+            .with_context(|| format!("not connected to {name}"))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client
+            .as_ref()
+            .is_some_and(|client| !client.is_closed())
+    }
+
+    pub fn is_healthy(&mut self) -> bool {
+        self.client
+            .as_mut()
+            .is_some_and(|client| client.is_valid(HEALTH_CHECK_TIMEOUT).is_ok())
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        if self.is_connected() {
+            ConnectionState::Connected
+        } else if self.error.is_some() {
+            ConnectionState::Unreachable
+        } else {
+            ConnectionState::Disconnected
+        }
+    }
+
+    pub fn connection_lost(&mut self, err: &anyhow::Error) -> bool {
+        let lost = !self.is_connected() || is_connection_error(err);
+        if lost {
+            self.client = None;
+        }
+        lost
+    }
+}
+
+fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|err| {
+        err.downcast_ref::<postgres::Error>().is_some_and(|err| {
+            // The server ends the session after a FATAL error, e.g. when the
+            // backend is terminated
+            err.is_closed()
+                || err
+                    .as_db_error()
+                    .is_some_and(|err| matches!(err.severity(), "FATAL" | "PANIC"))
+        }) || err.is::<std::io::Error>()
+    })
+}
+
+// An operation is only retried if the connection it ran on was lost: when it
+// was just established, trying again would only wait more
+fn retries(was_connected: bool, lost: bool) -> bool {
+    was_connected && lost
+}
+
+pub fn ensure_connected(db: &DatabasePtr) -> Result<()> {
+    if db.lock().unwrap().is_connected() {
+        return Ok(());
+    }
+    reconnect(db)
+}
+
+// Runs a read only operation, running it again if the connection was lost
+// while it ran: it's safe since it can't have changed anything
+pub fn run_read_only<T>(
+    db: &DatabasePtr,
+    mut operation: impl FnMut(&mut postgres::Client) -> Result<T>,
+) -> Result<T> {
+    let was_connected = db.lock().unwrap().is_connected();
+    ensure_connected(db)?;
+
+    {
+        let mut db = db.lock().unwrap();
+        match db.client().and_then(&mut operation) {
+            Err(err) if retries(was_connected, db.connection_lost(&err)) => {
+                log::warn!("connection to {} lost, retrying: {err:#}", db.name);
+            }
+            res => return res,
+        }
+    }
+
+    // Not locked while waiting to reconnect, for the state to be shown
+    reconnect(db)?;
+    db.lock().unwrap().client().and_then(operation)
+}
+
+// Reconnects to a database, retrying with increasing delays. The database is
+// only locked to install the new client, so that it can be used meanwhile.
+pub fn reconnect(db: &DatabasePtr) -> Result<()> {
+    let (name, config, tls) = {
+        let mut db = db.lock().unwrap();
+        db.client = None;
+        (db.name.clone(), db.config.clone(), db.tls.clone())
+    };
+    let mut last_error = None;
+
+    for (attempt, delay) in RECONNECT_DELAYS.into_iter().enumerate() {
+        std::thread::sleep(delay);
+        log::info!("reconnecting to database {name} (attempt {})", attempt + 1);
+
+        match config.connect(tls_connector(&tls)?) {
+            Ok(client) => {
+                log::info!("reconnected to database {name}");
+                let mut db = db.lock().unwrap();
+                db.client = Some(client);
+                db.error = None;
+                return Ok(());
+            }
+            Err(err) => {
+                log::warn!("error reconnecting to database {name}: {err}");
+                last_error = Some(err);
+            }
+        }
+    }
+
+    let err = last_error.expect("no reconnection attempt");
+    db.lock().unwrap().error = Some(err.to_string());
+    Err(err).with_context(|| format!("error reconnecting to {name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_lost() {
+        let mut config = postgres::Config::new();
+        config.host("127.0.0.1").port(1).user("postgres");
+        let Err(err) = config.connect(postgres::NoTls) else {
+            panic!("connected to a closed port");
+        };
+        let err = anyhow::Error::new(err).context("error running query");
+        assert!(is_connection_error(&err));
+        assert!(!is_connection_error(&anyhow::anyhow!(
+            "error parsing query plan"
+        )));
+
+        let mut db = Database {
+            name: "test".to_owned(),
+            config,
+            tls: TlsConfig::default(),
+            client: None,
+            error: None,
+        };
+        // Without a client, any error means the connection is gone
+        assert!(db.connection_lost(&anyhow::anyhow!("error preparing SQL query")));
+        assert_eq!(db.state(), ConnectionState::Disconnected);
+        assert!(db.client().is_err());
+
+        assert!(retries(true, true));
+        // Just connected: the server is reachable but the operation fails
+        assert!(!retries(false, true));
+        assert!(!retries(true, false));
+    }
+}
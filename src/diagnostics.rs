@@ -0,0 +1,133 @@
+use std::fmt::Write as _;
+
+use crate::config::Location;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, location: Option<&Location>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            location: location.cloned(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, location: Option<&Location>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+            location: location.cloned(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // Formats the diagnostic with the line of the file it's about, like
+    // compilers do
+    pub fn render(&self) -> String {
+        let text = self
+            .location
+            .as_ref()
+            .and_then(|location| std::fs::read_to_string(&location.file).ok())
+            .unwrap_or_default();
+        self.render_source(&text)
+    }
+
+    fn render_source(&self, text: &str) -> String {
+        let mut res = format!("{}: {}", self.severity, self.message);
+        let Some(location) = &self.location else {
+            return res;
+        };
+
+        let Some(line) = text.lines().nth(location.line - 1) else {
+            let _ = write!(res, "\n  --> {location}");
+            return res;
+        };
+
+        let number = location.line.to_string();
+        let margin = " ".repeat(number.len());
+        let column = location.column - 1;
+        // Spans of tables can cover several lines, only the first is shown
+        let width = text
+            .get(location.span.clone())
+            .map_or(1, |span| {
+                span.lines().next().unwrap_or_default().chars().count()
+            })
+            .clamp(1, line.chars().count().saturating_sub(column).max(1));
+        let _ = write!(
+            res,
+            "\n{margin}--> {location}\n{margin} |\n{number} | {line}\n{margin} | {}{}",
+            " ".repeat(column),
+            "^".repeat(width)
+        );
+        res
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(location) = &self.location {
+            write!(f, "{location}: ")?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+pub fn sort(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| {
+        let key = |d: &Diagnostic| {
+            (
+                d.severity,
+                d.location.as_ref().map(|l| (l.file.clone(), l.span.start)),
+            )
+        };
+        key(a).cmp(&key(b)).then_with(|| a.message.cmp(&b.message))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let text = "[user.links.Blogs]\nkind = \"blog\"\n";
+        let start = text.find("\"blog\"").unwrap();
+        let location = Location::new(Path::new("users.toml"), text, start..start + 6);
+        let diagnostic = Diagnostic::error("unknown resource blog", Some(&location));
+
+        assert_eq!(
+            diagnostic.render_source(text),
+            "error: unknown resource blog\n --> users.toml:2:8\n  |\n2 | kind = \"blog\"\n  |        ^^^^^^"
+        );
+        assert_eq!(
+            diagnostic.to_string(),
+            "users.toml:2:8: error: unknown resource blog"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+const DETAILS: [&str; 10] = [
+    "Sort Key",
+    "Group Key",
+    "Hash Cond",
+    "Merge Cond",
+    "Join Filter",
+    "Index Cond",
+    "Recheck Cond",
+    "Filter",
+    "One-Time Filter",
+    "Output",
+];
+
+// Renders the output of EXPLAIN (FORMAT JSON) as an indented tree, close to the
+// text format of EXPLAIN
+pub fn plan_lines(explain: &Value) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    let plans = explain.as_array().map(Vec::as_slice).unwrap_or_default();
+    for plan in plans {
+        if let Some(node) = plan.get("Plan") {
+            node_lines(node, 0, &mut lines);
+        }
+    }
+
+    lines
+}
+
+fn node_lines(node: &Value, depth: usize, lines: &mut Vec<String>) {
+    let indent = "      ".repeat(depth.saturating_sub(1));
+    let (prefix, details_indent) = if depth == 0 {
+        (String::new(), "  ".to_owned())
+    } else {
+        (format!("{indent}->  "), format!("{indent}        "))
+    };
+
+    let str_prop = |name: &str| node.get(name).and_then(Value::as_str);
+
+    let mut line = format!("{prefix}{}", str_prop("Node Type").unwrap_or("?"));
+    if let Some(index) = str_prop("Index Name") {
+        let _ = write!(&mut line, " using {index}");
+    }
+    if let Some(relation) = str_prop("Relation Name") {
+        let _ = write!(&mut line, " on {relation}");
+        if let Some(alias) = str_prop("Alias").filter(|alias| *alias != relation) {
+            let _ = write!(&mut line, " {alias}");
+        }
+    }
+    let float_prop = |name: &str| node.get(name).and_then(Value::as_f64);
+    if let (Some(startup), Some(total)) = (float_prop("Startup Cost"), float_prop("Total Cost")) {
+        let _ = write!(
+            &mut line,
+            "  (cost={startup:.2}..{total:.2} rows={} width={})",
+            node.get("Plan Rows").unwrap_or(&Value::Null),
+            node.get("Plan Width").unwrap_or(&Value::Null),
+        );
+    }
+    lines.push(line);
+
+    for name in DETAILS {
+        let value = match node.get(name) {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| item.as_str().map(str::to_owned).unwrap_or(item.to_string()))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Some(value) => value.to_string(),
+            None => continue,
+        };
+        lines.push(format!("{details_indent}{name}: {value}"));
+    }
+
+    let children = node
+        .get("Plans")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for child in children {
+        node_lines(child, depth + 1, lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_lines() {
+        let explain = serde_json::json!([{"Plan": {
+            "Node Type": "Hash Join",
+            "Startup Cost": 1.09,
+            "Total Cost": 2.21,
+            "Plan Rows": 5,
+            "Plan Width": 72,
+            "Hash Cond": "(p.blog_id = b.id)",
+            "Plans": [
+                {
+                    "Node Type": "Seq Scan",
+                    "Relation Name": "posts",
+                    "Alias": "p",
+                    "Startup Cost": 0.0,
+                    "Total Cost": 1.05,
+                    "Plan Rows": 5,
+                    "Plan Width": 40,
+                },
+                {
+                    "Node Type": "Index Scan",
+                    "Index Name": "blogs_pkey",
+                    "Relation Name": "blogs",
+                    "Alias": "blogs",
+                    "Startup Cost": 0.0,
+                    "Total Cost": 1.04,
+                    "Plan Rows": 1,
+                    "Plan Width": 32,
+                    "Index Cond": "(id = 1)",
+                },
+            ],
+        }}]);
+
+        assert_eq!(
+            plan_lines(&explain),
+            vec![
+                "Hash Join  (cost=1.09..2.21 rows=5 width=72)",
+                "  Hash Cond: (p.blog_id = b.id)",
+                "->  Seq Scan on posts p  (cost=0.00..1.05 rows=5 width=40)",
+                "->  Index Scan using blogs_pkey on blogs  (cost=0.00..1.04 rows=1 width=32)",
+                "        Index Cond: (id = 1)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{Context, Result};
+use jiff::tz::TimeZone;
+
+use crate::model::ColumnFormat;
+use crate::sql_value_as_string::SQLValueAsString;
+
+pub fn parse_timezone(name: &str) -> Result<TimeZone> {
+    if name == "local" {
+        return Ok(TimeZone::system());
+    }
+
+    TimeZone::get(name).with_context(|| format!("unknown time zone {name}"))
+}
+
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+fn number_value(row: &postgres::Row, idx: usize) -> Option<Number> {
+    let ty = row.columns()[idx].type_();
+
+    if ty == &postgres::types::Type::INT2 {
+        row.try_get::<_, Option<i16>>(idx)
+            .ok()?
+            .map(|v| Number::Int(v.into()))
+    } else if ty == &postgres::types::Type::INT4 {
+        row.try_get::<_, Option<i32>>(idx)
+            .ok()?
+            .map(|v| Number::Int(v.into()))
+    } else if ty == &postgres::types::Type::INT8 {
+        row.try_get::<_, Option<i64>>(idx).ok()?.map(Number::Int)
+    } else if ty == &postgres::types::Type::FLOAT4 {
+        row.try_get::<_, Option<f32>>(idx)
+            .ok()?
+            .map(|v| Number::Float(v.into()))
+    } else if ty == &postgres::types::Type::FLOAT8 {
+        row.try_get::<_, Option<f64>>(idx).ok()?.map(Number::Float)
+    } else {
+        None
+    }
+}
+
+fn timestamp_value(row: &postgres::Row, idx: usize) -> Option<jiff::Timestamp> {
+    if row.columns()[idx].type_() != &postgres::types::Type::TIMESTAMPTZ {
+        return None;
+    }
+
+    row.try_get::<_, Option<jiff::Timestamp>>(idx).ok()?
+}
+
+#[derive(Clone, Debug)]
+pub enum SortKey {
+    Int(i64),
+    Float(f64),
+    Timestamp(jiff::Timestamp),
+    Text(String),
+    // Last, like in PostgreSQL
+    Null,
+}
+
+impl SortKey {
+    pub fn new(row: &postgres::Row, idx: usize) -> Self {
+        if let Some(number) = number_value(row, idx) {
+            return match number {
+                Number::Int(n) => SortKey::Int(n),
+                Number::Float(n) => SortKey::Float(n),
+            };
+        }
+        if let Some(ts) = timestamp_value(row, idx) {
+            return SortKey::Timestamp(ts);
+        }
+
+        match row.try_get::<_, Option<SQLValueAsString>>(idx) {
+            Ok(Some(value)) => SortKey::Text(value.take_string()),
+            Ok(None) => SortKey::Null,
+            Err(err) => SortKey::Text(err.to_string()),
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            SortKey::Int(_) | SortKey::Float(_) => 0,
+            SortKey::Timestamp(_) => 1,
+            SortKey::Text(_) => 2,
+            SortKey::Null => 3,
+        }
+    }
+}
+
+impl Ord for SortKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (SortKey::Int(a), SortKey::Int(b)) => a.cmp(b),
+            (SortKey::Float(a), SortKey::Float(b)) => a.total_cmp(b),
+            (SortKey::Int(a), SortKey::Float(b)) => (*a as f64).total_cmp(b),
+            (SortKey::Float(a), SortKey::Int(b)) => a.total_cmp(&(*b as f64)),
+            (SortKey::Timestamp(a), SortKey::Timestamp(b)) => a.cmp(b),
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+            // A column has a single type, except for NULL
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+}
+
+impl PartialOrd for SortKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortKey {}
+
+fn group_thousands(digits: &str) -> String {
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", digits),
+    };
+    let mut res = String::new();
+
+    for (idx, c) in digits.chars().enumerate() {
+        if idx > 0 && (digits.len() - idx) % 3 == 0 {
+            res.push(',');
+        }
+        res.push(c);
+    }
+
+    format!("{sign}{res}")
+}
+
+fn format_number(value: f64, decimals: Option<usize>, thousands: bool) -> String {
+    let formatted = match decimals {
+        Some(decimals) => format!("{value:.decimals$}"),
+        None => value.to_string(),
+    };
+
+    if !thousands {
+        return formatted;
+    }
+
+    match formatted.split_once('.') {
+        Some((int_part, frac_part)) => format!("{}.{frac_part}", group_thousands(int_part)),
+        None => group_thousands(&formatted),
+    }
+}
+
+fn format_bytes(value: f64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = value;
+    let mut unit = 0;
+
+    while value.abs() >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{value} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+fn format_cents(cents: i64, currency: &str) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    let amount = format!(
+        "{}.{:02}",
+        group_thousands(&(cents / 100).to_string()),
+        cents % 100
+    );
+
+    match currency {
+        "USD" => format!("{sign}${amount}"),
+        "EUR" => format!("{sign}{amount} €"),
+        "GBP" => format!("{sign}£{amount}"),
+        currency => format!("{sign}{amount} {currency}"),
+    }
+}
+
+fn format_relative(seconds_ago: i64) -> String {
+    const UNITS: [(i64, &str); 6] = [
+        (365 * 24 * 3600, "year"),
+        (30 * 24 * 3600, "month"),
+        (24 * 3600, "day"),
+        (3600, "hour"),
+        (60, "minute"),
+        (1, "second"),
+    ];
+
+    let abs = seconds_ago.abs();
+    if abs < 1 {
+        return "now".to_owned();
+    }
+
+    let (unit_secs, unit) = UNITS
+        .iter()
+        .find(|(unit_secs, _)| abs >= *unit_secs)
+        .expect("missing time unit");
+    let count = abs / unit_secs;
+    let plural = if count > 1 { "s" } else { "" };
+
+    if seconds_ago > 0 {
+        format!("{count} {unit}{plural} ago")
+    } else {
+        format!("in {count} {unit}{plural}")
+    }
+}
+
+fn format_timestamp(ts: jiff::Timestamp, tz: TimeZone) -> String {
+    ts.to_zoned(tz).strftime("%Y-%m-%d %H:%M:%S %Z").to_string()
+}
+
+// Falls back to the raw value when the format does not apply to the column
+// type
+pub fn format_value(
+    row: &postgres::Row,
+    idx: usize,
+    format: Option<&ColumnFormat>,
+    default_timezone: Option<&TimeZone>,
+) -> String {
+    let formatted = match format {
+        Some(ColumnFormat::Timestamp { timezone }) => timestamp_value(row, idx).and_then(|ts| {
+            let tz = match timezone {
+                Some(name) => parse_timezone(name).ok()?,
+                None => default_timezone.cloned().unwrap_or(TimeZone::UTC),
+            };
+            Some(format_timestamp(ts, tz))
+        }),
+        Some(ColumnFormat::Relative) => timestamp_value(row, idx)
+            .map(|ts| format_relative(jiff::Timestamp::now().duration_since(ts).as_secs())),
+        Some(ColumnFormat::Number {
+            decimals,
+            thousands,
+        }) => number_value(row, idx).map(|n| match (n, decimals) {
+            (Number::Int(n), None) if *thousands => group_thousands(&n.to_string()),
+            (Number::Int(n), None) => n.to_string(),
+            (Number::Int(n), decimals) => format_number(n as f64, *decimals, *thousands),
+            (Number::Float(n), decimals) => format_number(n, *decimals, *thousands),
+        }),
+        Some(ColumnFormat::Bytes) => number_value(row, idx).map(|n| match n {
+            Number::Int(n) => format_bytes(n as f64),
+            Number::Float(n) => format_bytes(n),
+        }),
+        Some(ColumnFormat::Cents { currency }) => number_value(row, idx).and_then(|n| match n {
+            Number::Int(n) => Some(format_cents(n, currency)),
+            Number::Float(_) => None,
+        }),
+        None => default_timezone
+            .and_then(|tz| timestamp_value(row, idx).map(|ts| format_timestamp(ts, tz.clone()))),
+    };
+
+    formatted.unwrap_or_else(|| {
+        row.try_get::<_, SQLValueAsString>(idx)
+            .unwrap_or_else(|err| SQLValueAsString::new(err.to_string()))
+            .take_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands("1"), "1");
+        assert_eq!(group_thousands("1234"), "1,234");
+        assert_eq!(group_thousands("-1234567"), "-1,234,567");
+        assert_eq!(group_thousands("123456"), "123,456");
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(1234.5678, Some(2), true), "1,234.57");
+        assert_eq!(format_number(1234.5, None, false), "1234.5");
+        assert_eq!(format_number(-1234567.0, Some(0), true), "-1,234,567");
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(5.0 * 1024.0 * 1024.0 * 1024.0), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_format_cents() {
+        assert_eq!(format_cents(123456, "EUR"), "1,234.56 €");
+        assert_eq!(format_cents(-5, "USD"), "-$0.05");
+        assert_eq!(format_cents(100, "CHF"), "1.00 CHF");
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let mut keys = vec![
+            SortKey::Null,
+            SortKey::Int(10),
+            SortKey::Float(9.5),
+            SortKey::Int(-2),
+        ];
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                SortKey::Int(-2),
+                SortKey::Float(9.5),
+                SortKey::Int(10),
+                SortKey::Null
+            ]
+        );
+        assert!(SortKey::Text("b".to_owned()) < SortKey::Null);
+        assert!(
+            SortKey::Timestamp(jiff::Timestamp::UNIX_EPOCH)
+                < SortKey::Timestamp(jiff::Timestamp::MAX)
+        );
+    }
+
+    #[test]
+    fn test_format_relative() {
+        assert_eq!(format_relative(0), "now");
+        assert_eq!(format_relative(1), "1 second ago");
+        assert_eq!(format_relative(2 * 3600 + 5), "2 hours ago");
+        assert_eq!(format_relative(-3 * 24 * 3600), "in 3 days");
+    }
+}
@@ -0,0 +1,83 @@
+const MATCH_SCORE: i32 = 16;
+const CONSECUTIVE_BONUS: i32 = 8;
+const WORD_START_BONUS: i32 = 8;
+const GAP_PENALTY: i32 = 1;
+
+// Scores how well the pattern matches the text, ignoring case, or None if the
+// characters of the pattern are not all in the text in this order
+pub fn score(pattern: &str, text: &str) -> Option<i32> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    let is_match = |p: char, t: char| t.to_lowercase().eq(std::iter::once(p));
+    let bonus = |idx: usize| {
+        let is_word_start = idx == 0 || {
+            let (prev, c) = (chars[idx - 1], chars[idx]);
+            !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase())
+        };
+        MATCH_SCORE + if is_word_start { WORD_START_BONUS } else { 0 }
+    };
+
+    // Best score of the pattern so far, ending with a match at each position
+    let mut best: Vec<Option<i32>> = chars
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| is_match(pattern[0], *c).then(|| bonus(idx)))
+        .collect();
+    for p in &pattern[1..] {
+        let mut next = vec![None; chars.len()];
+        for (idx, c) in chars.iter().enumerate() {
+            if !is_match(*p, *c) {
+                continue;
+            }
+            next[idx] = (0..idx)
+                .filter_map(|prev| {
+                    let score = best[prev]?;
+                    let gap = idx - prev - 1;
+                    Some(if gap == 0 {
+                        score + CONSECUTIVE_BONUS
+                    } else {
+                        score - GAP_PENALTY * i32::try_from(gap).unwrap_or(i32::MAX)
+                    })
+                })
+                .max()
+                .map(|score| score + bonus(idx));
+        }
+        best = next;
+    }
+
+    best.into_iter().flatten().max()
+}
+
+// Indexes of the texts matching the pattern, best matches first, in their
+// original order for equal scores
+pub fn rank<'a>(pattern: &str, texts: impl IntoIterator<Item = &'a str>) -> Vec<usize> {
+    let mut scores: Vec<(usize, i32)> = texts
+        .into_iter()
+        .enumerate()
+        .filter_map(|(idx, text)| Some((idx, score(pattern, text)?)))
+        .collect();
+    scores.sort_by_key(|(idx, score)| (-score, *idx));
+    scores.into_iter().map(|(idx, _)| idx).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank() {
+        let labels = ["Comments", "Blog posts", "Subscriptions", "Tags of blogs"];
+
+        assert_eq!(rank("", labels), vec![0, 1, 2, 3]);
+        assert_eq!(rank("xyz", labels), Vec::<usize>::new());
+        // Initials beat scattered letters
+        assert_eq!(rank("bp", labels), vec![1, 2]);
+        assert_eq!(rank("BLOG", labels), vec![1, 3]);
+        assert_eq!(rank("tags", labels), vec![3]);
+        assert!(score("ctn", "Comments") < score("com", "Comments"));
+    }
+}
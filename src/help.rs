@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use crate::model::{ColumnExpression, Link, LinkCondition, Resource, Search, picker_order};
+
+pub fn resource_help(resource: &Resource) -> String {
+    let mut text = description_text(resource.description.as_deref());
+    let _ = writeln!(text, "Connection: {}", resource.connection_name());
+
+    let mut searches: Vec<(&String, &Search)> = resource.search.iter().collect();
+    searches.sort_by_key(|(_, s)| picker_order(s.order, s.position));
+    for (search_id, search) in searches {
+        let _ = write!(text, "\nSearch {search_id}:\n{}", search_help(search));
+    }
+
+    text
+}
+
+pub fn search_help(search: &Search) -> String {
+    let mut text = description_text(search.description.as_deref());
+    let _ = writeln!(text, "{}", search.query.trim());
+
+    if !search.params.is_empty() {
+        text.push_str("\nParameters:\n");
+        for (idx, param) in search.params.iter().enumerate() {
+            let ty = param.ty.as_ref().map(ToString::to_string);
+            let _ = write!(
+                text,
+                "  ${} {} ({})",
+                idx + 1,
+                &param.name,
+                ty.as_deref().unwrap_or("text")
+            );
+            match &param.description {
+                Some(description) => {
+                    let _ = writeln!(text, ": {description}");
+                }
+                None => text.push('\n'),
+            }
+        }
+    }
+
+    text
+}
+
+pub fn link_help(link: &Link, target: Option<&Resource>) -> String {
+    let mut text = description_text(link.description.as_deref());
+    let target_name = target.map_or(link.kind.as_str(), |r| r.name.as_str());
+    let _ = writeln!(text, "Shows {target_name} found by search {}", &link.search);
+    if let Some(LinkCondition::Eq(expr, value)) = &link.condition {
+        let _ = writeln!(
+            text,
+            "Only for rows where {} is {value}",
+            expression_text(expr)
+        );
+    }
+
+    let search = target.and_then(|r| r.search.get(&link.search));
+    if let Some(search) = search {
+        let _ = writeln!(text, "\n{}", search.query.trim());
+    }
+
+    if !link.search_params.is_empty() {
+        text.push_str("\nParameters:\n");
+        for (idx, expr) in link.search_params.iter().enumerate() {
+            let param = search.and_then(|s| s.params.get(idx));
+            let name = param.map_or("", |p| p.name.as_str());
+            let _ = writeln!(text, "  ${} {name} = {}", idx + 1, expression_text(expr));
+        }
+    }
+
+    if let Some(batch) = &link.batch {
+        let _ = writeln!(
+            text,
+            "\nFor several rows, search {} runs once for all of them",
+            &batch.search
+        );
+    }
+
+    text
+}
+
+fn description_text(description: Option<&str>) -> String {
+    description
+        .map(|description| format!("{}\n\n", description.trim()))
+        .unwrap_or_default()
+}
+
+fn expression_text(expr: &ColumnExpression) -> String {
+    match expr {
+        ColumnExpression::Name(column) => format!("column {column}"),
+        ColumnExpression::JsonPath {
+            col_and_path: (column, path),
+        } => format!("{path} in column {column}"),
+        ColumnExpression::Template { template } => format!("template \"{template}\""),
+        ColumnExpression::Value { value } => format!("value {value}"),
+        ColumnExpression::Prompt {
+            default: Some(default),
+            ..
+        } => format!("prompted, {default} by default"),
+        ColumnExpression::Prompt { default: None, .. } => "prompted".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_help() {
+        let blog: Resource = toml::from_str(
+            r#"
+            name = "Blog"
+            [search.editor]
+            query = "SELECT * FROM blogs WHERE editor_id = $1"
+            params = [{name = "User ID", type = "integer"}]
+            "#,
+        )
+        .unwrap();
+        let link: Link = toml::from_str(
+            r#"
+            kind = "blog"
+            description = "Blogs the user edits"
+            search = "editor"
+            search_params = ["id"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            link_help(&link, Some(&blog)),
+            "Blogs the user edits\n\n\
+             Shows Blog found by search editor\n\n\
+             SELECT * FROM blogs WHERE editor_id = $1\n\n\
+             Parameters:\n  $1 User ID = column id\n"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use cursive::style::{BaseColor, Color, Effect, Style};
+use cursive::utils::markup::StyledString;
+
+pub struct JsonLine {
+    pub text: StyledString,
+    // JSONPath of the value this line belongs to, closing brackets share the
+    // path of their opening line
+    pub path: String,
+    pub is_container: bool,
+}
+
+pub fn child_path(parent: &str, key: &str) -> String {
+    let is_identifier = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if is_identifier {
+        format!("{parent}.{key}")
+    } else {
+        format!(
+            "{parent}['{}']",
+            key.replace('\\', "\\\\").replace('\'', "\\'")
+        )
+    }
+}
+
+pub fn json_path_snippet(column: &str, path: &str) -> String {
+    format!(
+        "{{json_path = [{}, {}]}}",
+        toml::Value::String(column.to_owned()),
+        toml::Value::String(path.to_owned())
+    )
+}
+
+pub fn json_lines(value: &serde_json::Value, collapsed: &HashSet<String>) -> Vec<JsonLine> {
+    let mut lines = Vec::new();
+    push_lines(&mut lines, None, value, "$", 0, collapsed, false);
+    lines
+}
+
+fn key_style() -> Style {
+    Color::Dark(BaseColor::Blue).into()
+}
+
+fn scalar_style(value: &serde_json::Value) -> Style {
+    match value {
+        serde_json::Value::String(_) => Color::Dark(BaseColor::Green).into(),
+        serde_json::Value::Number(_) => Color::Dark(BaseColor::Cyan).into(),
+        _ => Color::Dark(BaseColor::Magenta).into(),
+    }
+}
+
+fn push_lines(
+    lines: &mut Vec<JsonLine>,
+    key: Option<&str>,
+    value: &serde_json::Value,
+    path: &str,
+    depth: usize,
+    collapsed: &HashSet<String>,
+    trailing_comma: bool,
+) {
+    let comma = if trailing_comma { "," } else { "" };
+    let mut text = StyledString::plain("  ".repeat(depth));
+
+    if let Some(key) = key {
+        text.append_styled(serde_json::Value::from(key).to_string(), key_style());
+        text.append_plain(": ");
+    }
+
+    let (children, open, close): (Vec<(Option<&str>, &serde_json::Value, String)>, _, _) =
+        match value {
+            serde_json::Value::Object(map) => (
+                map.iter()
+                    .map(|(k, v)| (Some(k.as_str()), v, child_path(path, k)))
+                    .collect(),
+                "{",
+                "}",
+            ),
+            serde_json::Value::Array(items) => (
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, v)| (None, v, format!("{path}[{idx}]")))
+                    .collect(),
+                "[",
+                "]",
+            ),
+            scalar => {
+                text.append_styled(scalar.to_string(), scalar_style(scalar));
+                text.append_plain(comma);
+                lines.push(JsonLine {
+                    text,
+                    path: path.to_owned(),
+                    is_container: false,
+                });
+                return;
+            }
+        };
+
+    if children.is_empty() || collapsed.contains(path) {
+        text.append_plain(open);
+        if !children.is_empty() {
+            text.append_styled("…", Effect::Dim);
+        }
+        text.append_plain(close);
+        text.append_plain(comma);
+        if !children.is_empty() {
+            text.append_styled(format!(" ({} items)", children.len()), Effect::Dim);
+        }
+        lines.push(JsonLine {
+            text,
+            path: path.to_owned(),
+            is_container: !children.is_empty(),
+        });
+        return;
+    }
+
+    text.append_plain(open);
+    lines.push(JsonLine {
+        text,
+        path: path.to_owned(),
+        is_container: true,
+    });
+
+    let n_children = children.len();
+    for (idx, (child_key, child, child_path)) in children.into_iter().enumerate() {
+        push_lines(
+            lines,
+            child_key,
+            child,
+            &child_path,
+            depth + 1,
+            collapsed,
+            idx + 1 < n_children,
+        );
+    }
+
+    lines.push(JsonLine {
+        text: StyledString::plain(format!("{}{close}{comma}", "  ".repeat(depth))),
+        path: path.to_owned(),
+        is_container: true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_path() {
+        assert_eq!(child_path("$", "postId"), "$.postId");
+        assert_eq!(child_path("$.a", "first name"), "$.a['first name']");
+        assert_eq!(child_path("$", "it's"), "$['it\\'s']");
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let value = serde_json::json!({"posts": [{"postId": 1}], "tags": ["a", "b"]});
+        let collapsed = HashSet::from(["$.tags".to_owned()]);
+        let lines = json_lines(&value, &collapsed);
+
+        assert_eq!(
+            lines
+                .iter()
+                .map(|l| (l.text.source(), l.path.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("{", "$"),
+                ("  \"posts\": [", "$.posts"),
+                ("    {", "$.posts[0]"),
+                ("      \"postId\": 1", "$.posts[0].postId"),
+                ("    }", "$.posts[0]"),
+                ("  ],", "$.posts"),
+                ("  \"tags\": […] (2 items)", "$.tags"),
+                ("}", "$"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_path_snippet() {
+        assert_eq!(
+            json_path_snippet("posts", "$[*].postId"),
+            r#"{json_path = ["posts", "$[*].postId"]}"#
+        );
+    }
+}
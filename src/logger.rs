@@ -0,0 +1,125 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Nothing is written to the terminal, since cursive owns it
+struct Logger {
+    level: LevelFilter,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // cursive logs a lot of debugging information about itself
+        if metadata.target().starts_with("cursive") {
+            return metadata.level() <= Level::Warn;
+        }
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap();
+            // There's nowhere to report errors writing to the log file
+            let _ = writeln!(
+                file,
+                "{} {:<5} {}",
+                jiff::Timestamp::now(),
+                record.level(),
+                record.args()
+            );
+        }
+
+        cursive::logger::log(record);
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+pub fn init(level: LevelFilter, log_file: Option<&Path>) -> Result<()> {
+    let file = log_file
+        .map(|path| {
+            File::options()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("error opening log file {}", path.display()))
+        })
+        .transpose()?;
+
+    log::set_boxed_logger(Box::new(Logger {
+        level,
+        file: file.map(Mutex::new),
+    }))
+    .context("error setting up logging")?;
+    log::set_max_level(level.max(LevelFilter::Warn));
+
+    // Panics would otherwise only be printed on the terminal, where the TUI
+    // hides them
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{info}");
+        log::logger().flush();
+        default_hook(info);
+    }));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logger() {
+        let path = std::env::temp_dir().join(format!("dbdrill-logger-{}.log", std::process::id()));
+        let file = File::create(&path).unwrap();
+        let logger = Logger {
+            level: LevelFilter::Info,
+            file: Some(Mutex::new(file)),
+        };
+        let log = |level: Level, target: &str, message: &str| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .args(format_args!("{message}"))
+                    .build(),
+            )
+        };
+
+        log(Level::Info, "dbdrill::db", "test query");
+        log(Level::Debug, "dbdrill::db", "test debug");
+        log(Level::Info, "cursive_core::cursive", "test cursive info");
+        log(Level::Warn, "cursive_core::cursive", "test cursive warning");
+        logger.flush();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let messages: Vec<&str> = text
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(messages, ["INFO  test query", "WARN  test cursive warning"]);
+
+        // The log viewer shows the same records
+        let logs = cursive::logger::LOGS.lock().unwrap();
+        let shown = |message: &str| logs.iter().any(|record| record.message == message);
+        assert!(shown("test query"));
+        assert!(shown("test cursive warning"));
+        assert!(!shown("test debug"));
+        assert!(!shown("test cursive info"));
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use lsp_server::{Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, Request as RequestTrait};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, Diagnostic,
+    DiagnosticSeverity, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+
+use crate::config::{Config, Location, check_config};
+use crate::diagnostics::Severity;
+use crate::model::{ColumnExpression, Connection, DEFAULT_CONNECTION, SearchParamType};
+use crate::template::{TemplatePart, parse_template};
+
+// Name of the configuration file looked up in the parent directories of the
+// edited files, for files included by another one
+const CONFIG_FILE_NAME: &str = "dbdrill.toml";
+
+struct Server {
+    config_path: Option<PathBuf>,
+    default_dsn: Option<String>,
+    documents: HashMap<PathBuf, String>,
+    roots: HashMap<PathBuf, PathBuf>,
+    // Last configuration loaded from each root, kept to complete while the
+    // files are being edited and don't parse
+    configs: HashMap<PathBuf, Config>,
+    // Documents with diagnostics, by root, to clear them when fixed
+    diagnosed: HashMap<PathBuf, HashSet<PathBuf>>,
+    dbs: HashMap<String, Option<postgres::Client>>,
+    columns: HashMap<String, Vec<String>>,
+}
+
+pub fn run(config_path: Option<PathBuf>, default_dsn: Option<String>) -> Result<()> {
+    let (connection, io_threads) = lsp_server::Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["\"".to_owned()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        config_path: config_path.map(|path| canonical(&path)),
+        default_dsn,
+        documents: HashMap::new(),
+        roots: HashMap::new(),
+        configs: HashMap::new(),
+        diagnosed: HashMap::new(),
+        dbs: HashMap::new(),
+        columns: HashMap::new(),
+    };
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = server.on_request(request);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                for params in server.on_notification(notification) {
+                    connection
+                        .sender
+                        .send(Message::Notification(Notification::new(
+                            PublishDiagnostics::METHOD.to_owned(),
+                            params,
+                        )))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+fn document_path(uri: &Url) -> Option<PathBuf> {
+    uri.to_file_path().ok().map(|path| canonical(&path))
+}
+
+impl Server {
+    fn on_request(&mut self, request: Request) -> Response {
+        match request.method.as_str() {
+            Completion::METHOD => match serde_json::from_value(request.params) {
+                Ok(params) => Response::new_ok(request.id, self.on_completion(params)),
+                Err(err) => Response::new_err(
+                    request.id,
+                    lsp_server::ErrorCode::InvalidParams as i32,
+                    err.to_string(),
+                ),
+            },
+            _ => Response::new_err(
+                request.id,
+                lsp_server::ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", request.method),
+            ),
+        }
+    }
+
+    fn on_notification(&mut self, notification: Notification) -> Vec<PublishDiagnosticsParams> {
+        let (path, text) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<lsp_types::DidOpenTextDocumentParams>(
+                    notification.params,
+                ) else {
+                    return Vec::new();
+                };
+                (
+                    document_path(&params.text_document.uri),
+                    Some(params.text_document.text),
+                )
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<lsp_types::DidChangeTextDocumentParams>(
+                    notification.params,
+                ) else {
+                    return Vec::new();
+                };
+                // Documents are synchronized in full, so the last change has
+                // the whole text
+                (
+                    document_path(&params.text_document.uri),
+                    params.content_changes.into_iter().last().map(|c| c.text),
+                )
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<lsp_types::DidCloseTextDocumentParams>(
+                    notification.params,
+                ) else {
+                    return Vec::new();
+                };
+                (document_path(&params.text_document.uri), None)
+            }
+            _ => return Vec::new(),
+        };
+        let Some(path) = path else {
+            return Vec::new();
+        };
+
+        match text {
+            Some(text) => {
+                self.documents.insert(path.clone(), text);
+                self.check(&path)
+            }
+            None => {
+                self.documents.remove(&path);
+                self.roots.remove(&path);
+                Vec::new()
+            }
+        }
+    }
+
+    fn root_candidates(&self, path: &Path) -> Vec<PathBuf> {
+        let mut candidates: Vec<PathBuf> = self
+            .config_path
+            .clone()
+            .or_else(|| {
+                path.ancestors()
+                    .skip(1)
+                    .map(|dir| dir.join(CONFIG_FILE_NAME))
+                    .find(|file| file.is_file())
+                    .map(|file| canonical(&file))
+            })
+            .into_iter()
+            .collect();
+        candidates.push(path.to_owned());
+        candidates
+    }
+
+    fn check(&mut self, path: &Path) -> Vec<PublishDiagnosticsParams> {
+        let mut root = path.to_owned();
+        let mut config = None;
+        let mut found = Vec::new();
+        for candidate in self.root_candidates(path) {
+            root = candidate;
+            (config, found) = check_config(&root, Some(&self.documents));
+            // Files not included by the configuration stand on their own
+            if config
+                .as_ref()
+                .is_none_or(|config| config.files.contains(&path.to_owned()))
+            {
+                break;
+            }
+        }
+        self.roots.insert(path.to_owned(), root.clone());
+        if let Some(config) = config {
+            self.configs.insert(root.clone(), config);
+        }
+
+        let mut diagnostics: HashMap<PathBuf, Vec<Diagnostic>> = HashMap::new();
+        diagnostics.insert(path.to_owned(), Vec::new());
+        for previous in self.diagnosed.remove(&root).unwrap_or_default() {
+            diagnostics.entry(previous).or_default();
+        }
+
+        for diagnostic in found {
+            let (file, range) = match &diagnostic.location {
+                Some(location) => {
+                    let file = canonical(&location.file);
+                    let range = self.location_range(&file, location);
+                    (file, range)
+                }
+                None => (root.clone(), Range::default()),
+            };
+            let severity = match diagnostic.severity {
+                Severity::Error => DiagnosticSeverity::ERROR,
+                Severity::Warning => DiagnosticSeverity::WARNING,
+            };
+            diagnostics
+                .entry(file.clone())
+                .or_default()
+                .push(Diagnostic {
+                    range,
+                    severity: Some(severity),
+                    source: Some("dbdrill".to_owned()),
+                    message: diagnostic.message,
+                    ..Default::default()
+                });
+            self.diagnosed.entry(root.clone()).or_default().insert(file);
+        }
+
+        diagnostics
+            .into_iter()
+            .filter_map(|(file, diagnostics)| {
+                Some(PublishDiagnosticsParams {
+                    uri: Url::from_file_path(file).ok()?,
+                    diagnostics,
+                    version: None,
+                })
+            })
+            .collect()
+    }
+
+    fn location_range(&self, file: &Path, location: &Location) -> Range {
+        let text = match self.documents.get(file) {
+            Some(text) => text.clone(),
+            None => std::fs::read_to_string(file).unwrap_or_default(),
+        };
+        Range::new(
+            position(&text, location.span.start),
+            position(&text, location.span.end),
+        )
+    }
+
+    fn on_completion(&mut self, params: CompletionParams) -> Vec<CompletionItem> {
+        let document = params.text_document_position;
+        let Some(path) = document_path(&document.text_document.uri) else {
+            return Vec::new();
+        };
+        let Some(text) = self.documents.get(&path) else {
+            return Vec::new();
+        };
+        let lines: Vec<&str> = text.split('\n').collect();
+        let Some(line) = lines.get(document.position.line as usize) else {
+            return Vec::new();
+        };
+        let prefix = utf16_prefix(line, document.position.character);
+        let Some((key, in_string)) = value_context(prefix) else {
+            return Vec::new();
+        };
+        let table = current_table(&lines[..=document.position.line as usize]);
+        let table_text = table_text(&lines, document.position.line as usize);
+        let key = key.to_owned();
+
+        let root = self.roots.get(&path).cloned().unwrap_or(path);
+        let Some(config) = self.configs.get(&root) else {
+            return Vec::new();
+        };
+
+        let link_kind = string_value(line, "kind")
+            .or_else(|| string_value(&table_text, "kind"))
+            .or_else(|| match table.as_slice() {
+                [resource_id, links, link_name, ..] if links == "links" => config
+                    .resources
+                    .get(resource_id)
+                    .and_then(|r| r.links.get(link_name))
+                    .map(|link| link.kind.clone()),
+                _ => None,
+            });
+
+        let (kind, names): (CompletionItemKind, Vec<(String, Option<String>)>) = match key.as_str()
+        {
+            "kind" => (
+                CompletionItemKind::CLASS,
+                config
+                    .resources
+                    .iter()
+                    .map(|(id, r)| (id.clone(), Some(r.name.clone())))
+                    .collect(),
+            ),
+            "search" => (
+                CompletionItemKind::FUNCTION,
+                link_kind
+                    .and_then(|kind| config.resources.get(&kind))
+                    .map(|r| {
+                        r.search
+                            .iter()
+                            .map(|(id, s)| (id.clone(), Some(s.query.clone())))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+            "search_params" | "json_path" | "column" => match table.first() {
+                Some(resource_id) => (
+                    CompletionItemKind::FIELD,
+                    self.resource_columns(&root, &resource_id.clone()),
+                ),
+                None => return Vec::new(),
+            },
+            "match_column" => match link_kind {
+                Some(kind) => (
+                    CompletionItemKind::FIELD,
+                    self.resource_columns(&root, &kind),
+                ),
+                None => return Vec::new(),
+            },
+            "type" if table.get(1).is_some_and(|t| t == "search") => (
+                CompletionItemKind::ENUM_MEMBER,
+                SearchParamType::NAMES
+                    .iter()
+                    .map(|name| (name.to_string(), None))
+                    .collect(),
+            ),
+            "connection" => (
+                CompletionItemKind::MODULE,
+                config
+                    .connections
+                    .keys()
+                    .cloned()
+                    .chain([DEFAULT_CONNECTION.to_owned()])
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .map(|name| (name, None))
+                    .collect(),
+            ),
+            _ => return Vec::new(),
+        };
+
+        let mut names = names;
+        names.sort();
+        names
+            .into_iter()
+            .map(|(name, detail)| CompletionItem {
+                insert_text: Some(if in_string {
+                    name.clone()
+                } else {
+                    toml::Value::String(name.clone()).to_string()
+                }),
+                label: name,
+                kind: Some(kind),
+                detail,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    // Columns of the rows of a resource: the ones named in its configuration,
+    // and the ones returned by its searches when the database is reachable
+    fn resource_columns(
+        &mut self,
+        root: &Path,
+        resource_id: &str,
+    ) -> Vec<(String, Option<String>)> {
+        let Some(config) = self.configs.get(root) else {
+            return Vec::new();
+        };
+        let Some(resource) = config.resources.get(resource_id) else {
+            return Vec::new();
+        };
+
+        let mut columns: Vec<String> = Vec::new();
+        columns.extend(resource.display.order.iter().cloned());
+        columns.extend(resource.display.hidden.iter().cloned());
+        columns.extend(resource.display.columns.keys().cloned());
+        columns.extend(resource.redact.keys().cloned());
+        if let Some(Ok(parts)) = resource.title.as_deref().map(parse_template) {
+            columns.extend(parts.into_iter().filter_map(|part| match part {
+                TemplatePart::Column(column) => Some(column),
+                TemplatePart::Literal(_) => None,
+            }));
+        }
+        for link in resource.links.values() {
+            columns.extend(link.search_params.iter().filter_map(|expr| match expr {
+                ColumnExpression::Name(column) => Some(column.clone()),
+                ColumnExpression::JsonPath {
+                    col_and_path: (column, _),
+                } => Some(column.clone()),
+                _ => None,
+            }));
+        }
+
+        let connection = resource.connection_name().to_owned();
+        let queries: Vec<String> = resource.search.values().map(|s| s.query.clone()).collect();
+        let connection_config = config.connections.get(&connection).cloned();
+        for query in queries {
+            columns.extend(self.query_columns(&connection, connection_config.as_ref(), &query));
+        }
+
+        columns.sort();
+        columns.dedup();
+        columns.into_iter().map(|column| (column, None)).collect()
+    }
+
+    fn query_columns(
+        &mut self,
+        connection_name: &str,
+        connection: Option<&Connection>,
+        query: &str,
+    ) -> Vec<String> {
+        if let Some(columns) = self.columns.get(query) {
+            return columns.clone();
+        }
+
+        let default_dsn = self.default_dsn.clone();
+        let client = self
+            .dbs
+            .entry(connection_name.to_owned())
+            .or_insert_with(|| {
+                connect(connection_name, connection, default_dsn)
+                    .inspect_err(|err| {
+                        log::warn!("not completing the columns of {connection_name}: {err:#}")
+                    })
+                    .ok()
+            });
+        let Some(client) = client else {
+            return Vec::new();
+        };
+
+        match client.prepare(query) {
+            Ok(statement) => {
+                let columns: Vec<String> = statement
+                    .columns()
+                    .iter()
+                    .map(|c| c.name().to_owned())
+                    .collect();
+                self.columns.insert(query.to_owned(), columns.clone());
+                columns
+            }
+            Err(err) => {
+                log::debug!("error preparing {query}: {err}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+// Connects to a database to describe queries, without prompting for a password
+// or opening SSH tunnels
+fn connect(
+    name: &str,
+    connection: Option<&Connection>,
+    default_dsn: Option<String>,
+) -> Result<postgres::Client> {
+    let (dsn, service) = match connection {
+        Some(connection) => {
+            if connection.ssh.is_some() {
+                anyhow::bail!("connections through SSH tunnels are not supported");
+            }
+            (connection.dsn()?, connection.service.clone())
+        }
+        None if name == DEFAULT_CONNECTION => (default_dsn, None),
+        None => anyhow::bail!("unknown connection {name}"),
+    };
+
+    let (mut config, tls) = crate::pgconfig::connection_config(dsn.as_deref(), service.as_deref())?;
+    if config.get_connect_timeout().is_none() {
+        config.connect_timeout(Duration::from_secs(5));
+    }
+    Ok(config.connect(crate::db::tls_connector(&tls)?)?)
+}
+
+// Key whose value the cursor is in, and whether it's in a string
+fn value_context(prefix: &str) -> Option<(&str, bool)> {
+    let eq = prefix.rfind('=')?;
+    let key = prefix[..eq].trim_end();
+    let start = key
+        .char_indices()
+        .rev()
+        .find(|(_, c)| !(c.is_ascii_alphanumeric() || *c == '_'))
+        .map_or(0, |(idx, c)| idx + c.len_utf8());
+    let key = &key[start..];
+
+    let value = &prefix[eq + 1..];
+    let in_string = value.matches('"').count() % 2 == 1;
+    let trimmed = value.trim_end();
+    if key.is_empty() || !(in_string || trimmed.is_empty() || trimmed.ends_with(['[', ','])) {
+        return None;
+    }
+
+    Some((key, in_string))
+}
+
+fn current_table(lines: &[&str]) -> Vec<String> {
+    let Some(header) = lines
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with('['))
+    else {
+        return Vec::new();
+    };
+    let header = header.trim_start_matches('[');
+    let header = header[..header.find(']').unwrap_or(header.len())].trim();
+
+    let mut keys = vec![String::new()];
+    let mut in_quotes = false;
+    for c in header.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '.' if !in_quotes => keys.push(String::new()),
+            c if in_quotes || !c.is_whitespace() => keys.last_mut().unwrap().push(c),
+            _ => {}
+        }
+    }
+    keys
+}
+
+fn table_text(lines: &[&str], line: usize) -> String {
+    let is_header = |l: &&str| l.trim().starts_with('[');
+    let start = lines[..=line]
+        .iter()
+        .rposition(is_header)
+        .map_or(0, |idx| idx + 1);
+    let end = lines[line + 1..]
+        .iter()
+        .position(is_header)
+        .map_or(lines.len(), |idx| line + 1 + idx);
+    lines[start..end].join("\n")
+}
+
+fn string_value(text: &str, key: &str) -> Option<String> {
+    text.match_indices(key).find_map(|(idx, _)| {
+        let before = text[..idx].chars().next_back();
+        if before.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+        let rest = text[idx + key.len()..].trim_start().strip_prefix('=')?;
+        let rest = rest.trim_start().strip_prefix('"')?;
+        Some(rest[..rest.find('"')?].to_owned())
+    })
+}
+
+// Start of a line, up to a position in UTF-16 code units like LSP positions
+fn utf16_prefix(line: &str, character: u32) -> &str {
+    let mut units = 0;
+    for (idx, c) in line.char_indices() {
+        if units >= character as usize {
+            return &line[..idx];
+        }
+        units += c.len_utf16();
+    }
+    line
+}
+
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_context() {
+        assert_eq!(value_context("kind = \""), Some(("kind", true)));
+        assert_eq!(value_context("kind = "), Some(("kind", false)));
+        assert_eq!(
+            value_context("search_params = [\"id\", "),
+            Some(("search_params", false))
+        );
+        assert_eq!(
+            value_context("params = [{name = \"ID\", type = \"in"),
+            Some(("type", true))
+        );
+        assert_eq!(value_context("kind = \"user\" "), None);
+        assert_eq!(value_context("[user.links.Blogs]"), None);
+    }
+
+    #[test]
+    fn test_current_table() {
+        let lines = [
+            "[user]",
+            "name = \"User\"",
+            "[user.links.\"Blog posts\"]",
+            "kind = \"post\"",
+        ];
+        assert_eq!(current_table(&lines), vec!["user", "links", "Blog posts"]);
+        assert_eq!(current_table(&lines[..2]), vec!["user"]);
+        assert_eq!(string_value(lines[3], "kind").as_deref(), Some("post"));
+    }
+}
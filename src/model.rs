@@ -1,73 +1,124 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use anyhow::{Context, Result, bail};
-use serde::Deserialize;
-
-#[derive(Clone, Debug, Deserialize)]
-pub enum SearchParamType {
-    #[serde(rename = "bool", alias = "boolean")]
-    Bool,
-    #[serde(rename = "bool[]", alias = "boolean[]")]
-    BoolArray,
-    #[serde(rename = "float4", alias = "real")]
-    Float4,
-    #[serde(rename = "float4[]", alias = "real[]")]
-    Float4Array,
-    #[serde(rename = "float8", alias = "double precision", alias = "double")]
-    Float8,
-    #[serde(rename = "float8[]", alias = "double precision[]", alias = "double[]")]
-    Float8Array,
-    #[serde(rename = "int2", alias = "smallint")]
-    Int2,
-    #[serde(rename = "int2[]", alias = "smallint[]")]
-    Int2Array,
-    #[serde(rename = "int4", alias = "integer", alias = "int")]
-    Int4,
-    #[serde(rename = "int4[]", alias = "integer[]", alias = "int[]")]
-    Int4Array,
-    #[serde(rename = "int8", alias = "bigint")]
-    Int8,
-    #[serde(rename = "int8[]", alias = "bigint[]")]
-    Int8Array,
-    #[serde(rename = "json")]
-    Json,
-    #[serde(rename = "jsonb")]
-    Jsonb,
-    #[serde(rename = "jsonb[]")]
-    JsonbArray,
-    #[serde(rename = "text", alias = "string")]
-    Text,
-    #[serde(rename = "text[]", alias = "string[]")]
-    TextArray,
-    #[serde(rename = "timestamptz", alias = "timestamp with time zone")]
-    Timestamptz,
-    #[serde(rename = "timestamptz[]", alias = "timestamp with time zone[]")]
-    TimestamptzArray,
-    #[serde(rename = "uuid")]
-    Uuid,
-    #[serde(rename = "uuid[]")]
-    UuidArray,
-    #[serde(rename = "varchar", alias = "character varying")]
-    Varchar,
-    #[serde(rename = "varchar[]", alias = "character varying[]")]
-    VarcharArray,
-}
-
-#[derive(Clone, Debug, Deserialize)]
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::diagnostics::Diagnostic;
+use crate::format::parse_timezone;
+use crate::template::parse_template;
+
+macro_rules! search_param_types {
+    ($($variant:ident => $name:literal $(| $alias:literal)*,)*) => {
+        #[derive(Clone, Debug, Deserialize, Serialize)]
+        pub enum SearchParamType {
+            $(
+                #[serde(rename = $name $(, alias = $alias)*)]
+                $variant,
+            )*
+        }
+
+        impl SearchParamType {
+            pub const NAMES: &[&str] = &[$($name, $($alias,)*)*];
+        }
+    };
+}
+
+search_param_types! {
+    Bool => "bool" | "boolean",
+    BoolArray => "bool[]" | "boolean[]",
+    Float4 => "float4" | "real",
+    Float4Array => "float4[]" | "real[]",
+    Float8 => "float8" | "double precision" | "double",
+    Float8Array => "float8[]" | "double precision[]" | "double[]",
+    Int2 => "int2" | "smallint",
+    Int2Array => "int2[]" | "smallint[]",
+    Int4 => "int4" | "integer" | "int",
+    Int4Array => "int4[]" | "integer[]" | "int[]",
+    Int8 => "int8" | "bigint",
+    Int8Array => "int8[]" | "bigint[]",
+    Json => "json",
+    Jsonb => "jsonb",
+    JsonbArray => "jsonb[]",
+    Text => "text" | "string",
+    TextArray => "text[]" | "string[]",
+    Timestamptz => "timestamptz" | "timestamp with time zone",
+    TimestamptzArray => "timestamptz[]" | "timestamp with time zone[]",
+    Uuid => "uuid",
+    UuidArray => "uuid[]",
+    Varchar => "varchar" | "character varying",
+    VarcharArray => "varchar[]" | "character varying[]",
+}
+
+impl SearchParamType {
+    pub fn is_array(&self) -> bool {
+        matches!(
+            self,
+            SearchParamType::BoolArray
+                | SearchParamType::Float4Array
+                | SearchParamType::Float8Array
+                | SearchParamType::Int2Array
+                | SearchParamType::Int4Array
+                | SearchParamType::Int8Array
+                | SearchParamType::JsonbArray
+                | SearchParamType::TextArray
+                | SearchParamType::TimestamptzArray
+                | SearchParamType::UuidArray
+                | SearchParamType::VarcharArray
+        )
+    }
+}
+
+// The derived schema would only list the main names, not the aliases
+impl JsonSchema for SearchParamType {
+    fn schema_name() -> std::borrow::Cow<'static, str> {
+        "SearchParamType".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "enum": SearchParamType::NAMES,
+        })
+    }
+}
+
+impl std::fmt::Display for SearchParamType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The serialized name is the one used in the configuration
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => f.write_str(&name),
+            _ => write!(f, "{self:?}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct SearchParam {
     pub name: String,
+    pub description: Option<String>,
     #[serde(rename = "type")]
     pub ty: Option<SearchParamType>,
+    // Values of sensitive parameters are hashed in the audit trail
+    #[serde(default)]
+    pub sensitive: bool,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct Search {
     pub query: String,
+    pub description: Option<String>,
     #[serde(default)]
     pub params: Vec<SearchParam>,
+    pub order: Option<u32>,
+    pub shortcut: Option<char>,
+    #[serde(skip)]
+    pub position: usize,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum ColumnExpression {
     Name(String),
@@ -75,108 +126,647 @@ pub enum ColumnExpression {
         #[serde(rename = "json_path")]
         col_and_path: (String, String),
     },
+    Template {
+        template: String,
+    },
+    Value {
+        value: serde_json::Value,
+    },
+    Prompt {
+        prompt: bool,
+        default: Option<String>,
+    },
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl ColumnExpression {
+    pub fn is_prompt(&self) -> bool {
+        matches!(self, ColumnExpression::Prompt { .. })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
 pub enum LinkCondition {
     #[serde(rename = "eq")]
     Eq(ColumnExpression, String),
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct LinkBatch {
+    pub search: String,
+    pub match_column: Option<MatchColumns>,
+}
+
+// Columns of the results holding the values of the search parameters: one for
+// the first parameter, or one per parameter, empty for those not returned
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum MatchColumns {
+    First(String),
+    PerParam(Vec<String>),
+}
+
+impl MatchColumns {
+    pub fn columns(&self) -> Vec<(usize, &str)> {
+        match self {
+            MatchColumns::First(col) => vec![(0, col.as_str())],
+            MatchColumns::PerParam(cols) => cols
+                .iter()
+                .enumerate()
+                .filter(|(_, col)| !col.is_empty())
+                .map(|(idx, col)| (idx, col.as_str()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct Link {
     pub kind: String,
+    pub description: Option<String>,
     pub search: String,
     pub search_params: Vec<ColumnExpression>,
     #[serde(rename = "if")]
     pub condition: Option<LinkCondition>,
+    pub batch: Option<LinkBatch>,
+    pub order: Option<u32>,
+    pub shortcut: Option<char>,
+    #[serde(skip)]
+    pub position: usize,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnAlign {
+    Left,
+    Center,
+    Right,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ColumnFormat {
+    Timestamp {
+        timezone: Option<String>,
+    },
+    Relative,
+    Number {
+        decimals: Option<usize>,
+        #[serde(default)]
+        thousands: bool,
+    },
+    Bytes,
+    Cents {
+        currency: String,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct ColumnDisplay {
+    pub label: Option<String>,
+    pub width: Option<usize>,
+    pub max_width: Option<usize>,
+    pub align: Option<ColumnAlign>,
+    pub format: Option<ColumnFormat>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct StyleRule {
+    #[serde(rename = "if")]
+    pub condition: Option<LinkCondition>,
+    // When set, only the cell of this column is styled instead of the whole row
+    pub column: Option<String>,
+    // Only apply the style to NULL cells, of any column if column is not set
+    #[serde(default)]
+    pub null: bool,
+    pub style: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct ResourceDisplay {
+    #[serde(default)]
+    pub order: Vec<String>,
+    #[serde(default)]
+    pub hidden: Vec<String>,
+    #[serde(default)]
+    pub columns: HashMap<String, ColumnDisplay>,
+    #[serde(default)]
+    pub styles: Vec<StyleRule>,
+}
+
+impl ResourceDisplay {
+    pub fn label<'a>(&'a self, column: &'a str) -> &'a str {
+        self.columns
+            .get(column)
+            .and_then(|c| c.label.as_deref())
+            .unwrap_or(column)
+    }
+}
+
+pub const DEFAULT_CONNECTION: &str = "default";
+
+// Keys taken by the pickers, which can't select items: ? shows the details of
+// the highlighted item, and q quits
+pub const RESERVED_SHORTCUTS: [char; 2] = ['?', 'q'];
+
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct Connection {
+    pub dsn: Option<String>,
+    pub dsn_env: Option<String>,
+    pub service: Option<String>,
+    pub ssh: Option<SshTunnel>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct SshTunnel {
+    // Destination of the ssh command, e.g. user@bastion.example.com or a host
+    // of ~/.ssh/config
+    pub host: String,
+    pub port: Option<u16>,
+    pub identity_file: Option<String>,
+}
+
+impl Connection {
+    pub fn dsn(&self) -> Result<Option<String>> {
+        match (&self.dsn, &self.dsn_env) {
+            (Some(dsn), None) => Ok(Some(dsn.clone())),
+            (None, Some(dsn_env)) => std::env::var(dsn_env)
+                .map(Some)
+                .with_context(|| format!("error reading environment variable {dsn_env}")),
+            (None, None) => Ok(None),
+            (Some(_), Some(_)) => bail!("dsn and dsn_env can't both be set"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Redaction {
+    Full,
+    Partial {
+        #[serde(default = "default_partial_keep")]
+        keep: usize,
+    },
+    // A hash of the value is shown, to compare values without seeing them
+    Hash,
+}
+
+fn default_partial_keep() -> usize {
+    4
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct Audit {
+    pub file: Option<PathBuf>,
+    pub connection: Option<String>,
+    // Table the entries are inserted in, in its entry column
+    #[serde(default = "default_audit_table")]
+    pub table: String,
+}
+
+fn default_audit_table() -> String {
+    "dbdrill_audit".to_owned()
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
 pub struct Resource {
     pub name: String,
+    pub description: Option<String>,
+    // Template identifying a row of this resource, for example "{name} <{email}>"
+    pub title: Option<String>,
     #[serde(default)]
     pub search: HashMap<String, Search>,
     #[serde(default)]
     pub links: HashMap<String, Link>,
+    #[serde(default)]
+    pub display: ResourceDisplay,
+    #[serde(default)]
+    pub redact: HashMap<String, Redaction>,
+    pub connection: Option<String>,
+    // Position in the resource picker: resources with an order come first,
+    // sorted by it, then the others in the order they are defined
+    pub order: Option<u32>,
+    pub shortcut: Option<char>,
+    // Rank of the definition in the configuration files, set when loading
+    #[serde(skip)]
+    pub position: usize,
 }
 
-fn validate_resource_link(resources: &HashMap<String, Resource>, link: &Link) -> Result<()> {
-    let Some(target_resource) = resources.get(&link.kind) else {
-        bail!("link references a non existing resource {}", &link.kind);
-    };
-
-    let Some(target_search) = target_resource.search.get(&link.search) else {
-        bail!(
-            "referenced resource {} has no search named {}",
-            &link.kind,
-            &link.search
-        );
-    };
+pub fn picker_order(order: Option<u32>, position: usize) -> (bool, Option<u32>, usize) {
+    (order.is_none(), order, position)
+}
 
-    if target_search.params.len() != link.search_params.len() {
-        bail!(
-            "referenced search {} has {} params but link specifies {}",
-            &link.search,
-            target_search.params.len(),
-            link.search_params.len()
-        );
+impl Resource {
+    pub fn connection_name(&self) -> &str {
+        self.connection.as_deref().unwrap_or(DEFAULT_CONNECTION)
     }
+}
 
-    for (idx, p) in link.search_params.iter().enumerate() {
-        if let ColumnExpression::JsonPath {
+fn validate_column_expression(expr: &ColumnExpression) -> Result<()> {
+    match expr {
+        ColumnExpression::Name(_) | ColumnExpression::Value { .. } => {}
+        ColumnExpression::JsonPath {
             col_and_path: (_, path),
-        } = p
-        {
-            jsonpath_rust::parser::parse_json_path(path).with_context(|| {
-                format!("invalid JSONPath expression for search parameter {idx}")
-            })?;
+        } => {
+            jsonpath_rust::parser::parse_json_path(path).context("invalid JSONPath expression")?;
+        }
+        ColumnExpression::Template { template } => {
+            parse_template(template)?;
+        }
+        ColumnExpression::Prompt { prompt, .. } => {
+            if !prompt {
+                bail!("prompt can only be set to true");
+            }
         }
     }
 
-    if let Some(LinkCondition::Eq(
-        ColumnExpression::JsonPath {
-            col_and_path: (_, path),
-        },
-        _,
-    )) = &link.condition
-    {
-        jsonpath_rust::parser::parse_json_path(path)
-            .context("link condition (\"if\") is an invalid JSONPath expression")?;
+    Ok(())
+}
+
+fn validate_condition(condition: &LinkCondition) -> Result<()> {
+    let LinkCondition::Eq(expr, _) = condition;
+    if expr.is_prompt() {
+        bail!("conditions (\"if\") can't use a prompted value");
     }
+    validate_column_expression(expr).context("invalid condition (\"if\")")
+}
 
-    Ok(())
+struct Validator<'a> {
+    config: &'a Config,
+    diagnostics: Vec<Diagnostic>,
 }
 
-fn validate_resource_links(
-    resources: &HashMap<String, Resource>,
-    links: &HashMap<String, Link>,
-) -> Result<()> {
-    for (link_name, link) in links {
-        validate_resource_link(resources, link)
-            .with_context(|| format!("error validating link {link_name}"))?;
+impl Validator<'_> {
+    fn error(&mut self, key: &str, message: String) {
+        let location = self.config.location(key);
+        self.diagnostics.push(Diagnostic::error(message, location));
+    }
+
+    fn warning(&mut self, key: &str, message: String) {
+        let location = self.config.location(key);
+        self.diagnostics
+            .push(Diagnostic::warning(message, location));
+    }
+
+    fn validate_resource(&mut self, resource_id: &str, resource: &Resource) {
+        if resource.name.is_empty() {
+            self.error(
+                &format!("{resource_id}.name"),
+                format!("resource {resource_id} has an empty name"),
+            );
+        }
+
+        if let Some(connection) = &resource.connection
+            && connection != DEFAULT_CONNECTION
+            && !self.config.connections.contains_key(connection)
+        {
+            self.error(
+                &format!("{resource_id}.connection"),
+                format!("resource {resource_id} uses an unknown connection {connection}"),
+            );
+        }
+
+        if let Some(title) = &resource.title
+            && let Err(err) = parse_template(title)
+        {
+            self.error(
+                &format!("{resource_id}.title"),
+                format!("invalid title: {err:#}"),
+            );
+        }
+
+        for (link_name, link) in &resource.links {
+            self.validate_link(&format!("{resource_id}.links.{link_name}"), link_name, link);
+        }
+
+        self.check_shortcuts(
+            resource
+                .search
+                .iter()
+                .map(|(name, search)| (format!("{resource_id}.search.{name}"), search.shortcut))
+                .collect(),
+        );
+        self.check_shortcuts(
+            resource
+                .links
+                .iter()
+                .map(|(name, link)| (format!("{resource_id}.links.{name}"), link.shortcut))
+                .collect(),
+        );
+
+        for (column, column_display) in &resource.display.columns {
+            if let Some(ColumnFormat::Timestamp {
+                timezone: Some(timezone),
+            }) = &column_display.format
+                && let Err(err) = parse_timezone(timezone)
+            {
+                self.error(
+                    &format!("{resource_id}.display.columns.{column}.format.timezone"),
+                    format!("{err:#}"),
+                );
+            }
+        }
+
+        for (idx, rule) in resource.display.styles.iter().enumerate() {
+            let key = format!("{resource_id}.display.styles[{idx}]");
+            if rule.style.parse::<cursive::style::Style>().is_err() {
+                self.error(
+                    &format!("{key}.style"),
+                    format!("invalid style {}", &rule.style),
+                );
+            }
+            if let Some(condition) = &rule.condition
+                && let Err(err) = validate_condition(condition)
+            {
+                self.error(&format!("{key}.if"), format!("{err:#}"));
+            }
+        }
+    }
+
+    fn check_shortcuts(&mut self, mut entries: Vec<(String, Option<char>)>) {
+        entries.sort();
+        let mut used: HashMap<char, String> = HashMap::new();
+        for (key, shortcut) in entries {
+            let Some(shortcut) = shortcut else {
+                continue;
+            };
+            // Control characters, like Esc going back, are no keys to pick either
+            if RESERVED_SHORTCUTS.contains(&shortcut) || shortcut.is_control() {
+                self.error(
+                    &format!("{key}.shortcut"),
+                    format!(
+                        "shortcut {} is reserved by the pickers",
+                        shortcut.escape_default()
+                    ),
+                );
+                continue;
+            }
+            match used.get(&shortcut) {
+                Some(other_key) => self.error(
+                    &format!("{key}.shortcut"),
+                    format!("shortcut {shortcut} is already used by {other_key}"),
+                ),
+                None => {
+                    used.insert(shortcut, key);
+                }
+            }
+        }
+    }
+
+    fn validate_link(&mut self, key: &str, link_name: &str, link: &Link) {
+        for (idx, param) in link.search_params.iter().enumerate() {
+            if let Err(err) = validate_column_expression(param) {
+                self.error(
+                    &format!("{key}.search_params[{idx}]"),
+                    format!("invalid value for search parameter {idx}: {err:#}"),
+                );
+            }
+        }
+
+        if let Some(condition) = &link.condition
+            && let Err(err) = validate_condition(condition)
+        {
+            self.error(&format!("{key}.if"), format!("{err:#}"));
+        }
+
+        let Some(target_resource) = self.config.resources.get(&link.kind) else {
+            self.error(
+                &format!("{key}.kind"),
+                format!(
+                    "link {link_name} references a non existing resource {}",
+                    &link.kind
+                ),
+            );
+            return;
+        };
+
+        match target_resource.search.get(&link.search) {
+            None => self.error(
+                &format!("{key}.search"),
+                format!(
+                    "referenced resource {} has no search named {}",
+                    &link.kind, &link.search
+                ),
+            ),
+            Some(search) if search.params.len() != link.search_params.len() => self.error(
+                &format!("{key}.search_params"),
+                format!(
+                    "referenced search {} has {} params but link specifies {}",
+                    &link.search,
+                    search.params.len(),
+                    link.search_params.len()
+                ),
+            ),
+            Some(_) => {}
+        }
+
+        let Some(batch) = &link.batch else {
+            return;
+        };
+        let batch_key = format!("{key}.batch.search");
+        let Some(batch_search) = target_resource.search.get(&batch.search) else {
+            self.error(
+                &batch_key,
+                format!(
+                    "referenced resource {} has no search named {}",
+                    &link.kind, &batch.search
+                ),
+            );
+            return;
+        };
+
+        if batch_search.params.len() != link.search_params.len() {
+            self.error(
+                &batch_key,
+                format!(
+                    "referenced batch search {} has {} params but link specifies {}",
+                    &batch.search,
+                    batch_search.params.len(),
+                    link.search_params.len()
+                ),
+            );
+        }
+
+        if let Some(MatchColumns::PerParam(cols)) = &batch.match_column {
+            if cols.len() > batch_search.params.len() {
+                self.error(
+                    &batch_key,
+                    format!(
+                        "match_column lists {} columns but batch search {} has {} params",
+                        cols.len(),
+                        &batch.search,
+                        batch_search.params.len()
+                    ),
+                );
+            }
+        }
+
+        for param in &batch_search.params {
+            if !param.ty.as_ref().is_some_and(SearchParamType::is_array) {
+                self.error(
+                    &batch_key,
+                    format!(
+                        "parameter {} of batch search {} must have an array type",
+                        &param.name, &batch.search
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_usage(&mut self) {
+        let resources = &self.config.resources;
+        let links: Vec<&Link> = resources.values().flat_map(|r| r.links.values()).collect();
+
+        for (resource_id, resource) in resources {
+            let linked = links.iter().any(|link| &link.kind == resource_id);
+            if !linked && resource.search.is_empty() {
+                self.warning(
+                    resource_id,
+                    format!(
+                        "resource {resource_id} has no search and no link points to it, so it can't be displayed"
+                    ),
+                );
+            }
+
+            // Searches only taking arrays are meant for batch links. The
+            // others can be run from the search picker, they are never unused.
+            for (search_id, search) in &resource.search {
+                let batch_only = !search.params.is_empty()
+                    && search
+                        .params
+                        .iter()
+                        .all(|param| param.ty.as_ref().is_some_and(SearchParamType::is_array));
+                let used = links.iter().any(|link| {
+                    &link.kind == resource_id
+                        && (&link.search == search_id
+                            || link.batch.as_ref().is_some_and(|b| &b.search == search_id))
+                });
+                if batch_only && !used {
+                    self.warning(
+                        &format!("{resource_id}.search.{search_id}"),
+                        format!("search {search_id} only has array parameters but no link uses it"),
+                    );
+                }
+            }
+
+            let mut link_names: Vec<&String> = resource.links.keys().collect();
+            link_names.sort();
+            for (idx, link_name) in link_names.iter().enumerate() {
+                let link = &resource.links[*link_name];
+                let shadowed = link_names[..idx].iter().find(|other| {
+                    let other = &resource.links[**other];
+                    other.kind == link.kind
+                        && other.search == link.search
+                        && other.search_params == link.search_params
+                        && other.condition == link.condition
+                });
+                if let Some(other_name) = shadowed {
+                    self.warning(
+                        &format!("{resource_id}.links.{link_name}"),
+                        format!(
+                            "link {link_name} shows the same rows as link {other_name}, with the same search and parameters"
+                        ),
+                    );
+                }
+            }
+        }
     }
-    Ok(())
 }
 
-pub fn validate_resources(resources: &HashMap<String, Resource>) -> Result<()> {
+fn location_suffix(config: &Config, key: &str) -> String {
+    config
+        .location(key)
+        .map(|l| format!(" ({l})"))
+        .unwrap_or_default()
+}
+
+pub fn validate_resources(config: &Config) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        config,
+        diagnostics: Vec::new(),
+    };
+
+    // Sorted to report resources with the same name the same way every time
+    let mut resource_ids: Vec<&String> = config.resources.keys().collect();
+    resource_ids.sort();
     let mut used_names: HashMap<&str, &str> = HashMap::new();
 
-    for (resource_id, resource) in resources {
+    for resource_id in resource_ids {
+        let resource = &config.resources[resource_id];
         if resource_id.is_empty() {
-            bail!("resource identifiers can't be empty");
+            validator.error(
+                resource_id,
+                "resource identifiers can't be empty".to_owned(),
+            );
         }
 
-        if resource.name.is_empty() {
-            bail!("resource {resource_id} has an empty name");
+        if let Some(other_resource_id) = used_names.insert(&resource.name, resource_id) {
+            validator.error(
+                &format!("{resource_id}.name"),
+                format!(
+                    "resource {resource_id} has the same name as {other_resource_id}{}",
+                    location_suffix(config, other_resource_id)
+                ),
+            );
         }
 
-        if let Some(other_resource_id) = used_names.insert(&resource.name, resource_id) {
-            bail!("resource {resource_id} has the same name as {other_resource_id}");
+        validator.validate_resource(resource_id, resource);
+    }
+
+    validator.check_shortcuts(
+        config
+            .resources
+            .iter()
+            .map(|(resource_id, resource)| (resource_id.clone(), resource.shortcut))
+            .collect(),
+    );
+
+    for (name, connection) in &config.connections {
+        let key = format!("connections.{name}");
+        if connection.dsn.is_some() && connection.dsn_env.is_some() {
+            validator.error(
+                &format!("{key}.dsn_env"),
+                format!("connection {name} can't have both dsn and dsn_env set"),
+            );
+        }
+        if connection.dsn.is_none() && connection.dsn_env.is_none() && connection.service.is_none()
+        {
+            validator.error(
+                &key,
+                format!("connection {name} must have one of dsn, dsn_env or service set"),
+            );
+        }
+        if connection
+            .ssh
+            .as_ref()
+            .is_some_and(|ssh| ssh.host.is_empty())
+        {
+            validator.error(
+                &format!("{key}.ssh.host"),
+                format!("the SSH host of connection {name} can't be empty"),
+            );
+        }
+    }
+
+    if let Some(audit) = &config.audit {
+        if audit.file.is_some() == audit.connection.is_some() {
+            validator.error(
+                "audit",
+                "audit must have exactly one of file or connection set".to_owned(),
+            );
         }
 
-        validate_resource_links(resources, &resource.links)
-            .with_context(|| format!("error validating {resource_id}.links"))?;
+        if let Some(connection) = &audit.connection
+            && connection != DEFAULT_CONNECTION
+            && !config.connections.contains_key(connection)
+        {
+            validator.error(
+                "audit.connection",
+                format!("audit uses an unknown connection {connection}"),
+            );
+        }
     }
-    Ok(())
+
+    validator.check_usage();
+    validator.diagnostics
 }
@@ -0,0 +1,47 @@
+use sha2::{Digest, Sha256};
+
+use crate::model::Redaction;
+
+// Shown instead of fully redacted values, whatever their length
+pub const MASK: &str = "********";
+
+pub fn redact(value: &str, redaction: &Redaction) -> String {
+    match redaction {
+        Redaction::Full => MASK.to_owned(),
+        Redaction::Partial { keep } => {
+            let len = value.chars().count();
+            // Values too short to be partially shown are masked entirely
+            let masked = if len <= *keep { len } else { len - keep };
+            value
+                .chars()
+                .enumerate()
+                .map(|(idx, c)| if idx < masked { '*' } else { c })
+                .collect()
+        }
+        Redaction::Hash => hash(value),
+    }
+}
+
+pub fn hash(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256:{hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let partial = Redaction::Partial { keep: 4 };
+        assert_eq!(redact("+33612345678", &partial), "********5678");
+        assert_eq!(redact("1234", &partial), "****");
+        assert_eq!(redact("", &partial), "");
+        assert_eq!(redact("secret", &Redaction::Full), MASK);
+        assert_eq!(
+            redact("foo@example.com", &Redaction::Hash),
+            "sha256:321ba197033e81286fedb719d60d4ed5cecaed170733cb4a92013811afc0e3b6"
+        );
+    }
+}
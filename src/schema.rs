@@ -0,0 +1,67 @@
+use schemars::generate::SchemaSettings;
+use schemars::{Schema, json_schema};
+use serde_json::Value;
+
+use crate::model::{Audit, Connection, Resource};
+
+// JSON Schema of the configuration files, for editors completing and checking
+// TOML files with a schema, like taplo (Even Better TOML in VS Code)
+pub fn config_schema() -> Schema {
+    let mut generator = SchemaSettings::draft07().for_deserialize().into_generator();
+    let connection = generator.subschema_for::<Connection>();
+    let audit = generator.subschema_for::<Audit>();
+    let resource = generator.subschema_for::<Resource>();
+    let mut definitions = generator.take_definitions(true);
+
+    // A resource can be split across files, so that a file can hold only some
+    // of its searches or links
+    if let Some(resource) = definitions
+        .get_mut("Resource")
+        .and_then(Value::as_object_mut)
+    {
+        resource.remove("required");
+    }
+
+    json_schema!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "dbdrill configuration",
+        "type": "object",
+        "properties": {
+            "include": {
+                "type": "array",
+                "items": {"type": "string"},
+            },
+            "connections": {
+                "type": "object",
+                "additionalProperties": connection,
+            },
+            "audit": audit,
+        },
+        "additionalProperties": resource,
+        "definitions": definitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::SearchParamType;
+
+    #[test]
+    fn test_config_schema_up_to_date() {
+        let schema = serde_json::to_string_pretty(&config_schema()).unwrap() + "\n";
+        assert!(
+            schema == include_str!("../dbdrill.schema.json"),
+            "dbdrill.schema.json is outdated, run: dbdrill schema > dbdrill.schema.json"
+        );
+    }
+
+    #[test]
+    fn test_search_param_type_names() {
+        for &name in SearchParamType::NAMES {
+            let ty: SearchParamType = serde_json::from_value(Value::from(name))
+                .unwrap_or_else(|_| panic!("invalid type name {name}"));
+            assert!(SearchParamType::NAMES.contains(&ty.to_string().as_str()));
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::cmp::Ordering;
+use std::hash::Hash;
+
+use cursive::align::HAlign;
+use cursive::style::Style;
+use cursive::view::ViewWrapper;
+use cursive::view::scroll::Scroller;
+use cursive::views::NamedView;
+use cursive::{Printer, View};
+use cursive_table_view::{TableView, TableViewItem};
+
+pub trait StyledTableItem<H>: TableViewItem<H>
+where
+    H: Eq + Hash + Copy + Clone + Send + Sync + 'static,
+{
+    fn column_style(&self, column: H) -> Option<Style>;
+}
+
+pub struct ColumnLayout<H> {
+    pub column: H,
+    pub width: usize,
+    pub align: HAlign,
+}
+
+// Layout of cursive_table_view: the header and its separator are above the
+// rows, and the columns are separated by a space, a bar and a space
+const HEADER_HEIGHT: usize = 2;
+const COLUMN_SEPARATOR_WIDTH: usize = 3;
+
+// cursive_table_view can only draw plain strings. This wraps a table whose
+// columns all have a fixed width, and draws the styled cells again on top of
+// it once the table is drawn.
+//
+// The table doesn't tell which item is drawn on which row, so the wrapper
+// sorts the rows the same way when the order or the items of the table change.
+// For both to agree, the cmp implementation of the items must never return
+// Equal for two different items.
+pub struct StyledTable<T, H> {
+    view: NamedView<TableView<T, H>>,
+    columns: Vec<ColumnLayout<H>>,
+    rows_to_items: Vec<usize>,
+    sorted_by: Option<(H, Ordering)>,
+}
+
+impl<T, H> StyledTable<T, H>
+where
+    T: StyledTableItem<H> + Send + Sync + 'static,
+    H: Eq + Hash + Copy + Clone + Send + Sync + 'static,
+{
+    pub fn new(view: NamedView<TableView<T, H>>, columns: Vec<ColumnLayout<H>>) -> Self {
+        StyledTable {
+            view,
+            columns,
+            rows_to_items: Vec::new(),
+            sorted_by: None,
+        }
+    }
+
+    fn update_rows(&mut self) {
+        let rows_to_items = &mut self.rows_to_items;
+        let sorted_by = &mut self.sorted_by;
+
+        self.view.with_view(|table| {
+            // The focused row is the only one whose item the table tells
+            let focus_moved = table
+                .row()
+                .zip(table.item())
+                .is_some_and(|(row, item)| rows_to_items.get(row) != Some(&item));
+            if *sorted_by != table.order() || rows_to_items.len() != table.len() || focus_moved {
+                *rows_to_items = sorted_rows(table);
+                *sorted_by = table.order();
+            }
+        });
+    }
+}
+
+fn sorted_rows<T, H>(table: &TableView<T, H>) -> Vec<usize>
+where
+    T: StyledTableItem<H>,
+    H: Eq + Hash + Copy + Clone + Send + Sync + 'static,
+{
+    let mut rows_to_items: Vec<usize> = (0..table.len()).collect();
+    if let Some((column, order)) = table.order() {
+        rows_to_items.sort_by(|a, b| {
+            let (a, b) = (
+                table.borrow_item(*a).unwrap(),
+                table.borrow_item(*b).unwrap(),
+            );
+            if order == Ordering::Less {
+                a.cmp(b, column)
+            } else {
+                b.cmp(a, column)
+            }
+        });
+    }
+
+    rows_to_items
+}
+
+fn aligned(value: &str, width: usize, align: HAlign) -> String {
+    match align {
+        HAlign::Left => format!("{value:<width$} "),
+        HAlign::Right => format!("{value:>width$} "),
+        HAlign::Center => format!("{value:^width$} "),
+    }
+}
+
+fn draw_styles<T, H>(
+    table: &TableView<T, H>,
+    printer: &Printer,
+    columns: &[ColumnLayout<H>],
+    rows_to_items: &[usize],
+) where
+    T: StyledTableItem<H>,
+    H: Eq + Hash + Copy + Clone + Send + Sync + 'static,
+{
+    let viewport = table.get_scroller().content_viewport();
+    let printer = printer
+        .offset((0, HEADER_HEIGHT))
+        .cropped((viewport.width(), viewport.height()));
+
+    for y in 0..viewport.height() {
+        let row = viewport.top() + y;
+        if table.row() == Some(row) {
+            continue;
+        }
+
+        let Some(item) = rows_to_items.get(row).and_then(|i| table.borrow_item(*i)) else {
+            break;
+        };
+
+        let mut x = 0;
+        for column in columns {
+            if let Some(style) = item.column_style(column.column) {
+                let text = aligned(&item.to_column(column.column), column.width, column.align);
+                // Long values are clipped by the next column separator
+                let printer = printer.offset((x, y)).cropped((column.width + 1, 1));
+                printer.with_style(style, |printer| printer.print((0, 0), &text));
+            }
+            x += column.width + COLUMN_SEPARATOR_WIDTH;
+        }
+    }
+}
+
+impl<T, H> ViewWrapper for StyledTable<T, H>
+where
+    T: StyledTableItem<H> + Send + Sync + 'static,
+    H: Eq + Hash + Copy + Clone + Send + Sync + 'static,
+{
+    cursive::wrap_impl!(self.view: NamedView<TableView<T, H>>);
+
+    fn wrap_layout(&mut self, size: cursive::Vec2) {
+        self.view.layout(size);
+        self.update_rows();
+    }
+
+    fn wrap_draw(&self, printer: &Printer) {
+        self.view.draw(printer);
+        self.view
+            .with_view(|table| draw_styles(table, printer, &self.columns, &self.rows_to_items));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    struct Column;
+
+    #[derive(Clone)]
+    struct Item(i32, usize);
+
+    impl TableViewItem<Column> for Item {
+        fn to_column(&self, _column: Column) -> String {
+            self.0.to_string()
+        }
+
+        fn cmp(&self, other: &Self, _column: Column) -> Ordering {
+            self.0.cmp(&other.0).then(self.1.cmp(&other.1))
+        }
+    }
+
+    impl StyledTableItem<Column> for Item {
+        fn column_style(&self, _column: Column) -> Option<Style> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_sorted_rows() {
+        let mut table = TableView::<Item, Column>::new().column(Column, "n", |c| c);
+        let check = |table: &mut TableView<Item, Column>| {
+            for (row, item) in sorted_rows(table).into_iter().enumerate() {
+                table.set_selected_row(row);
+                assert_eq!(table.item(), Some(item));
+            }
+        };
+
+        table.set_items(
+            [3, 1, 2, 1]
+                .into_iter()
+                .enumerate()
+                .map(|(i, n)| Item(n, i))
+                .collect(),
+        );
+        check(&mut table);
+        table.sort_by(Column, Ordering::Greater);
+        check(&mut table);
+        table.insert_item(Item(2, 4));
+        check(&mut table);
+        assert_eq!(sorted_rows(&table), [0, 4, 2, 3, 1]);
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::{Result, bail};
+
+#[derive(Debug, PartialEq)]
+pub enum TemplatePart {
+    Literal(String),
+    Column(String),
+}
+
+pub fn parse_template(template: &str) -> Result<Vec<TemplatePart>> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut column = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => column.push(c),
+                        None => bail!("unterminated placeholder in template {template:?}"),
+                    }
+                }
+
+                if column.is_empty() {
+                    bail!("empty placeholder in template {template:?}");
+                }
+
+                if !literal.is_empty() {
+                    parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                }
+                parts.push(TemplatePart::Column(column));
+            }
+            '}' => bail!("unmatched '}}' in template {template:?}"),
+            c => literal.push(c),
+        }
+    }
+
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(literal));
+    }
+
+    Ok(parts)
+}
+
+pub fn render_template(
+    template: &str,
+    mut column_value: impl FnMut(&str) -> Result<String>,
+) -> Result<String> {
+    let mut res = String::new();
+
+    for part in parse_template(template)? {
+        match part {
+            TemplatePart::Literal(s) => res.push_str(&s),
+            TemplatePart::Column(col) => res.push_str(&column_value(&col)?),
+        }
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        assert_eq!(
+            parse_template("{org_id}:{user_id} {{x}}").unwrap(),
+            vec![
+                TemplatePart::Column("org_id".to_owned()),
+                TemplatePart::Literal(":".to_owned()),
+                TemplatePart::Column("user_id".to_owned()),
+                TemplatePart::Literal(" {x}".to_owned()),
+            ]
+        );
+        assert!(parse_template("{org_id").is_err());
+        assert!(parse_template("{}").is_err());
+        assert!(parse_template("a}b").is_err());
+    }
+
+    #[test]
+    fn test_render_template() {
+        let rendered = render_template("{name} <{email}>", |col| Ok(col.to_uppercase())).unwrap();
+        assert_eq!(rendered, "NAME <EMAIL>");
+    }
+}
@@ -1,11 +1,12 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 
 use crate::{json_helpers::extract_single_value, model::SearchParamType};
 
-pub fn sql_value_from_string(
-    str_val: &str,
-    ty: SearchParamType,
-) -> Result<Box<dyn postgres::types::ToSql + Sync>> {
+// A value bound to a query parameter. Values are Send so that the parameters
+// of a query can be kept around to run it again, for example to explain it.
+pub type SqlValue = Box<dyn postgres::types::ToSql + Send + Sync>;
+
+pub fn sql_value_from_string(str_val: &str, ty: SearchParamType) -> Result<SqlValue> {
     match ty {
         SearchParamType::Bool => {
             let bool_val: bool = str_val
@@ -148,7 +149,7 @@ pub fn sql_value_from_string(
 pub fn sql_value_from_json_slice(
     val: &[&serde_json::Value],
     ty: SearchParamType,
-) -> Result<Box<dyn postgres::types::ToSql + Sync>> {
+) -> Result<SqlValue> {
     match ty {
         SearchParamType::Bool => Ok(Box::new(
             extract_single_value(val)?
@@ -307,3 +308,394 @@ pub fn sql_value_from_json_slice(
         )),
     }
 }
+
+pub fn sql_value_from_literal(val: &serde_json::Value, ty: SearchParamType) -> Result<SqlValue> {
+    match (val, ty) {
+        (_, SearchParamType::Json | SearchParamType::Jsonb) => Ok(Box::new(val.clone())),
+        (serde_json::Value::String(s), ty) => sql_value_from_string(s, ty),
+        (serde_json::Value::Array(items), ty) => {
+            sql_value_from_json_slice(&items.iter().collect::<Vec<_>>(), ty)
+        }
+        (val, ty) => sql_value_from_json_slice(&[val], ty),
+    }
+}
+
+// Value of a column, read with its type to bind it to a query as is. Integers
+// and floats are widened, they are narrowed again to the parameter's type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Json(serde_json::Value),
+    Timestamp(jiff::Timestamp),
+    Uuid(uuid::Uuid),
+}
+
+impl std::fmt::Display for ParamValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamValue::Null => f.write_str("<NULL>"),
+            ParamValue::Bool(b) => write!(f, "{b}"),
+            ParamValue::Int(i) => write!(f, "{i}"),
+            ParamValue::Float(x) => write!(f, "{x}"),
+            ParamValue::Text(s) => f.write_str(s),
+            ParamValue::Json(serde_json::Value::String(s)) => f.write_str(s),
+            ParamValue::Json(v) => write!(f, "{v}"),
+            ParamValue::Timestamp(ts) => write!(f, "{ts}"),
+            ParamValue::Uuid(uuid) => write!(f, "{uuid}"),
+        }
+    }
+}
+
+impl postgres::types::FromSql<'_> for ParamValue {
+    fn from_sql(
+        ty: &postgres::types::Type,
+        raw: &[u8],
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        use postgres::types::Type;
+
+        let value = match *ty {
+            Type::BOOL => ParamValue::Bool(bool::from_sql(ty, raw)?),
+            Type::INT2 => ParamValue::Int(i16::from_sql(ty, raw)?.into()),
+            Type::INT4 => ParamValue::Int(i32::from_sql(ty, raw)?.into()),
+            Type::INT8 => ParamValue::Int(i64::from_sql(ty, raw)?),
+            Type::FLOAT4 => ParamValue::Float(f32::from_sql(ty, raw)?.into()),
+            Type::FLOAT8 => ParamValue::Float(f64::from_sql(ty, raw)?),
+            Type::TEXT | Type::VARCHAR => ParamValue::Text(String::from_sql(ty, raw)?),
+            Type::JSON | Type::JSONB => ParamValue::Json(serde_json::Value::from_sql(ty, raw)?),
+            Type::TIMESTAMPTZ => ParamValue::Timestamp(jiff::Timestamp::from_sql(ty, raw)?),
+            Type::UUID => ParamValue::Uuid(uuid::Uuid::from_sql(ty, raw)?),
+            _ => return Err(format!("unsupported type: {ty}").into()),
+        };
+        Ok(value)
+    }
+
+    fn from_sql_null(
+        _ty: &postgres::types::Type,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(ParamValue::Null)
+    }
+
+    fn accepts(ty: &postgres::types::Type) -> bool {
+        search_param_type(ty).is_some_and(|ty| !ty.is_array())
+    }
+}
+
+impl From<&serde_json::Value> for ParamValue {
+    fn from(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => ParamValue::Null,
+            serde_json::Value::Bool(b) => ParamValue::Bool(*b),
+            serde_json::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => ParamValue::Int(i),
+                (None, Some(x)) => ParamValue::Float(x),
+                (None, None) => ParamValue::Json(value.clone()),
+            },
+            serde_json::Value::String(s) => ParamValue::Text(s.clone()),
+            value => ParamValue::Json(value.clone()),
+        }
+    }
+}
+
+// Conversion of a non null value to the Rust type bound for a parameter type
+trait FromParamValue: Sized {
+    fn from_param_value(value: &ParamValue) -> Option<Self>;
+}
+
+impl FromParamValue for bool {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Bool(b) => Some(*b),
+            ParamValue::Text(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+fn int_from_param_value<T: TryFrom<i64> + std::str::FromStr>(value: &ParamValue) -> Option<T> {
+    match value {
+        ParamValue::Int(i) => T::try_from(*i).ok(),
+        ParamValue::Text(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+impl FromParamValue for i16 {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        int_from_param_value(value)
+    }
+}
+
+impl FromParamValue for i32 {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        int_from_param_value(value)
+    }
+}
+
+impl FromParamValue for i64 {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        int_from_param_value(value)
+    }
+}
+
+impl FromParamValue for f32 {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        f64::from_param_value(value).map(|x| x as f32)
+    }
+}
+
+impl FromParamValue for f64 {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Float(x) => Some(*x),
+            ParamValue::Int(i) => Some(*i as f64),
+            ParamValue::Text(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromParamValue for String {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        Some(value.to_string())
+    }
+}
+
+impl FromParamValue for serde_json::Value {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Json(v) => Some(v.clone()),
+            ParamValue::Bool(b) => Some((*b).into()),
+            ParamValue::Int(i) => Some((*i).into()),
+            ParamValue::Float(x) => Some((*x).into()),
+            value => Some(value.to_string().into()),
+        }
+    }
+}
+
+impl FromParamValue for jiff::Timestamp {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Timestamp(ts) => Some(*ts),
+            ParamValue::Text(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromParamValue for uuid::Uuid {
+    fn from_param_value(value: &ParamValue) -> Option<Self> {
+        match value {
+            ParamValue::Uuid(uuid) => Some(*uuid),
+            ParamValue::Text(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+fn convert_param_value<T: FromParamValue>(
+    value: &ParamValue,
+    type_name: &str,
+) -> Result<Option<T>> {
+    if *value == ParamValue::Null {
+        return Ok(None);
+    }
+    T::from_param_value(value)
+        .map(Some)
+        .with_context(|| format!("can't convert value to {type_name}: {value}"))
+}
+
+fn scalar_from_param_value<T>(value: &ParamValue, type_name: &str) -> Result<SqlValue>
+where
+    T: FromParamValue + postgres::types::ToSql + Send + Sync + 'static,
+{
+    Ok(Box::new(convert_param_value::<T>(value, type_name)?))
+}
+
+fn array_from_param_values<T>(values: &[ParamValue], type_name: &str) -> Result<SqlValue>
+where
+    T: FromParamValue + postgres::types::ToSql + Send + Sync + 'static,
+{
+    let values = values
+        .iter()
+        .map(|value| convert_param_value::<T>(value, type_name))
+        .collect::<Result<Vec<Option<T>>>>()?;
+    Ok(Box::new(values))
+}
+
+pub fn sql_value_from_param_value(value: &ParamValue, ty: SearchParamType) -> Result<SqlValue> {
+    match ty {
+        SearchParamType::Bool => scalar_from_param_value::<bool>(value, "bool"),
+        SearchParamType::Float4 => scalar_from_param_value::<f32>(value, "float4"),
+        SearchParamType::Float8 => scalar_from_param_value::<f64>(value, "float8"),
+        SearchParamType::Int2 => scalar_from_param_value::<i16>(value, "int2"),
+        SearchParamType::Int4 => scalar_from_param_value::<i32>(value, "int4"),
+        SearchParamType::Int8 => scalar_from_param_value::<i64>(value, "int8"),
+        SearchParamType::Json | SearchParamType::Jsonb => {
+            scalar_from_param_value::<serde_json::Value>(value, "json")
+        }
+        SearchParamType::Text | SearchParamType::Varchar => {
+            scalar_from_param_value::<String>(value, "text")
+        }
+        SearchParamType::Timestamptz => {
+            scalar_from_param_value::<jiff::Timestamp>(value, "timestamptz")
+        }
+        SearchParamType::Uuid => scalar_from_param_value::<uuid::Uuid>(value, "uuid"),
+        ty => sql_array_from_param_values(std::slice::from_ref(value), ty),
+    }
+}
+
+pub fn sql_array_from_param_values(values: &[ParamValue], ty: SearchParamType) -> Result<SqlValue> {
+    match ty {
+        SearchParamType::BoolArray => array_from_param_values::<bool>(values, "bool"),
+        SearchParamType::Float4Array => array_from_param_values::<f32>(values, "float4"),
+        SearchParamType::Float8Array => array_from_param_values::<f64>(values, "float8"),
+        SearchParamType::Int2Array => array_from_param_values::<i16>(values, "int2"),
+        SearchParamType::Int4Array => array_from_param_values::<i32>(values, "int4"),
+        SearchParamType::Int8Array => array_from_param_values::<i64>(values, "int8"),
+        SearchParamType::JsonbArray => array_from_param_values::<serde_json::Value>(values, "json"),
+        SearchParamType::TextArray | SearchParamType::VarcharArray => {
+            array_from_param_values::<String>(values, "text")
+        }
+        SearchParamType::TimestamptzArray => {
+            array_from_param_values::<jiff::Timestamp>(values, "timestamptz")
+        }
+        SearchParamType::UuidArray => array_from_param_values::<uuid::Uuid>(values, "uuid"),
+        ty => bail!("can't build an array for non array type {ty:?}"),
+    }
+}
+
+pub fn search_param_type(ty: &postgres::types::Type) -> Option<SearchParamType> {
+    use postgres::types::Type;
+
+    let res = match *ty {
+        Type::BOOL => SearchParamType::Bool,
+        Type::BOOL_ARRAY => SearchParamType::BoolArray,
+        Type::FLOAT4 => SearchParamType::Float4,
+        Type::FLOAT4_ARRAY => SearchParamType::Float4Array,
+        Type::FLOAT8 => SearchParamType::Float8,
+        Type::FLOAT8_ARRAY => SearchParamType::Float8Array,
+        Type::INT2 => SearchParamType::Int2,
+        Type::INT2_ARRAY => SearchParamType::Int2Array,
+        Type::INT4 => SearchParamType::Int4,
+        Type::INT4_ARRAY => SearchParamType::Int4Array,
+        Type::INT8 => SearchParamType::Int8,
+        Type::INT8_ARRAY => SearchParamType::Int8Array,
+        Type::JSON => SearchParamType::Json,
+        Type::JSONB => SearchParamType::Jsonb,
+        Type::JSONB_ARRAY => SearchParamType::JsonbArray,
+        Type::TEXT => SearchParamType::Text,
+        Type::TEXT_ARRAY => SearchParamType::TextArray,
+        Type::TIMESTAMPTZ => SearchParamType::Timestamptz,
+        Type::TIMESTAMPTZ_ARRAY => SearchParamType::TimestamptzArray,
+        Type::UUID => SearchParamType::Uuid,
+        Type::UUID_ARRAY => SearchParamType::UuidArray,
+        Type::VARCHAR => SearchParamType::Varchar,
+        Type::VARCHAR_ARRAY => SearchParamType::VarcharArray,
+        _ => return None,
+    };
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use postgres::types::{FromSql, Type};
+
+    use super::*;
+
+    // Encodes a bound value as it would be sent for a parameter of the type
+    fn encode(value: &SqlValue, ty: &Type) -> Result<Vec<u8>> {
+        let mut buf = bytes::BytesMut::new();
+        value
+            .to_sql_checked(ty, &mut buf)
+            .map_err(|err| anyhow::anyhow!(err))?;
+        Ok(buf.to_vec())
+    }
+
+    #[test]
+    fn test_bind_column_values() {
+        // Values are bound with the type of their column
+        let raw = 5_000_000_000i64.to_be_bytes();
+        let value = ParamValue::from_sql(&Type::INT8, &raw).unwrap();
+        assert_eq!(value, ParamValue::Int(5_000_000_000));
+        let ty = search_param_type(&Type::INT8).unwrap();
+        let bound = sql_value_from_param_value(&value, ty).unwrap();
+        assert_eq!(encode(&bound, &Type::INT8).unwrap(), raw);
+
+        let uuid: uuid::Uuid = "67e55044-10b1-426f-9247-bb680e5fe0c8".parse().unwrap();
+        let value = ParamValue::from_sql(&Type::UUID, uuid.as_bytes()).unwrap();
+        assert_eq!(value, ParamValue::Uuid(uuid));
+        let bound = sql_value_from_param_value(&value, SearchParamType::Uuid).unwrap();
+        assert_eq!(encode(&bound, &Type::UUID).unwrap(), uuid.as_bytes());
+
+        // NULLs stay NULLs of the parameter type
+        let value = ParamValue::from_sql_nullable(&Type::INT8, None).unwrap();
+        let bound = sql_value_from_param_value(&value, SearchParamType::Int8).unwrap();
+        assert_eq!(format!("{bound:?}"), "None");
+        assert!(encode(&bound, &Type::INT8).is_ok());
+
+        // Values are narrowed to the parameter type when they fit
+        let bound =
+            sql_value_from_param_value(&ParamValue::Int(42), SearchParamType::Int4).unwrap();
+        assert_eq!(encode(&bound, &Type::INT4).unwrap(), 42i32.to_be_bytes());
+        assert!(
+            sql_value_from_param_value(&ParamValue::Int(1 << 40), SearchParamType::Int4).is_err()
+        );
+
+        // Columns of other types can't be bound
+        assert!(!<ParamValue as FromSql>::accepts(&Type::POINT));
+        assert!(!<ParamValue as FromSql>::accepts(&Type::INT4_ARRAY));
+    }
+
+    #[test]
+    fn test_bind_arrays() {
+        // NULLs are kept as NULL elements instead of failing to parse
+        let values = [ParamValue::Int(1), ParamValue::Null, ParamValue::Int(3)];
+        let bound = sql_array_from_param_values(&values, SearchParamType::Int8Array).unwrap();
+        assert_eq!(format!("{bound:?}"), "[Some(1), None, Some(3)]");
+
+        let ts: jiff::Timestamp = "2024-05-01T12:00:00Z".parse().unwrap();
+        let values = [
+            ParamValue::Timestamp(ts),
+            ParamValue::Text("2024-05-02T00:00:00Z".to_owned()),
+        ];
+        let bound =
+            sql_array_from_param_values(&values, SearchParamType::TimestamptzArray).unwrap();
+        assert_eq!(
+            format!("{bound:?}"),
+            "[Some(2024-05-01T12:00:00Z), Some(2024-05-02T00:00:00Z)]"
+        );
+
+        let values = [ParamValue::Float(1.5), ParamValue::Int(2)];
+        let bound = sql_array_from_param_values(&values, SearchParamType::Float8Array).unwrap();
+        assert_eq!(format!("{bound:?}"), "[Some(1.5), Some(2.0)]");
+
+        // JSON values are bound as documents, even strings
+        let values = [
+            ParamValue::Json(serde_json::json!({"a": 1})),
+            ParamValue::from(&serde_json::json!("b")),
+        ];
+        let bound = sql_array_from_param_values(&values, SearchParamType::JsonbArray).unwrap();
+        assert_eq!(
+            format!("{bound:?}"),
+            r#"[Some(Object {"a": Number(1)}), Some(String("b"))]"#
+        );
+
+        // Untyped parameters are bound as text
+        let values = [ParamValue::Int(1), ParamValue::Bool(true)];
+        let bound = sql_array_from_param_values(&values, SearchParamType::TextArray).unwrap();
+        assert_eq!(format!("{bound:?}"), r#"[Some("1"), Some("true")]"#);
+
+        assert!(
+            sql_array_from_param_values(
+                &[ParamValue::Text("x".to_owned())],
+                SearchParamType::Int4Array
+            )
+            .is_err()
+        );
+        assert!(sql_array_from_param_values(&[], SearchParamType::Int4).is_err());
+    }
+}
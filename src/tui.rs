@@ -1,45 +1,379 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use cursive::View;
-use cursive::view::{Nameable, Resizable};
+use cursive::style::Style;
+use cursive::utils::markup::StyledString;
+use cursive::view::{Nameable, Resizable, Scrollable};
 use cursive::views::{self};
+use jiff::tz::TimeZone;
 use jsonpath_rust::JsonPath;
 
+use crate::audit::AuditEntry;
+use crate::clipboard::copy_to_clipboard;
+use crate::config::{Config, Location, append_search, check_config};
+use crate::db::{self, ConnectionState, Database, DatabasePtr};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::explain::plan_lines;
+use crate::format::{SortKey, format_value};
+use crate::fuzzy;
+use crate::help::{link_help, resource_help, search_help};
 use crate::json_helpers::extract_single_value;
-use crate::model::{ColumnExpression, LinkCondition, Resource, SearchParamType};
+use crate::json_tree::{json_lines, json_path_snippet};
+use crate::model::{
+    Audit, ColumnAlign, ColumnExpression, Connection, Link, LinkCondition, MatchColumns,
+    RESERVED_SHORTCUTS, Redaction, Resource, ResourceDisplay, Search, SearchParam, SearchParamType,
+    StyleRule, picker_order,
+};
+use crate::redact::{MASK, redact};
 use crate::sql_value_as_string::SQLValueAsString;
-use crate::to_sql::{sql_value_from_json_slice, sql_value_from_string};
+use crate::styled_table::{ColumnLayout, StyledTable, StyledTableItem};
+use crate::template::{TemplatePart, parse_template, render_template};
+use crate::to_sql::{
+    ParamValue, SqlValue, search_param_type, sql_array_from_param_values,
+    sql_value_from_json_slice, sql_value_from_literal, sql_value_from_param_value,
+    sql_value_from_string,
+};
+use crate::watcher::wait_for_change;
+
+pub struct Settings {
+    pub default_timezone: Option<TimeZone>,
+    pub statement_timeout: Duration,
+    pub unmask: bool,
+    pub picker_mode: PickerMode,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, clap::ValueEnum)]
+pub enum PickerMode {
+    // Shortcuts while every item has a key, else a filter box
+    Auto,
+    Shortcuts,
+    Filter,
+}
 
 struct AppData {
     resources: HashMap<String, Resource>,
-    db: postgres::Client,
+    locations: HashMap<String, Location>,
+    // Files the configuration was last loaded from, watched for changes
+    config_files: Vec<PathBuf>,
+    // Changes to the connections and the audit settings require a restart, the
+    // ones loaded at startup are kept to tell when they changed
+    connections: HashMap<String, Connection>,
+    audit: Option<Audit>,
+    dbs: HashMap<String, DatabasePtr>,
+    // Last known state of the databases, kept here to show it without waiting
+    // for the queries running on them
+    connection_states: HashMap<String, ConnectionState>,
+    connection_status: views::TextContent,
+    settings: Settings,
 }
 
 type AppDataPtr = Arc<Mutex<AppData>>;
 
-pub fn start(db: postgres::Client, resources: HashMap<String, Resource>) {
+pub fn start(
+    dbs: HashMap<String, Database>,
+    config: Config,
+    config_path: PathBuf,
+    settings: Settings,
+) {
     let mut siv = cursive::default();
     siv.add_global_callback('q', |s| s.quit());
 
-    let app_data_ptr = Arc::new(Mutex::new(AppData { resources, db }));
+    let connection_status = views::TextContent::new("");
+
+    // Routes are mounted as layers of the routes stack, above the status line
+    // at the bottom of the screen. Dialogs are layers of the screen, on top.
+    siv.add_fullscreen_layer(
+        views::LinearLayout::vertical()
+            .child(views::StackView::new().with_name(ROUTES).full_screen())
+            .child(
+                views::LinearLayout::horizontal()
+                    .child(views::TextView::new("").with_name("status").full_width())
+                    .child(views::TextView::new_with_content(connection_status.clone())),
+            ),
+    );
+
+    let connection_states = dbs
+        .iter()
+        .map(|(name, db)| (name.clone(), db.state()))
+        .collect();
+    let app_data_ptr = Arc::new(Mutex::new(AppData {
+        resources: config.resources,
+        locations: config.locations,
+        config_files: config.files,
+        connections: config.connections,
+        audit: config.audit,
+        dbs: dbs
+            .into_iter()
+            .map(|(name, db)| (name, Arc::new(Mutex::new(db))))
+            .collect(),
+        connection_states,
+        connection_status,
+        settings,
+    }));
+    update_connection_status(&app_data_ptr.lock().unwrap());
     let router = Router::new(Arc::clone(&app_data_ptr));
     router.push(&mut siv, Box::new(RouteResourcePicker {}));
+    {
+        let router = router.clone();
+        siv.add_global_callback(':', move |siv| on_sql_console(siv, &router));
+    }
+    {
+        let router = router.clone();
+        siv.add_global_callback('~', move |siv| router.push(siv, Box::new(LogRoute {})));
+    }
+    {
+        let app_data_ptr = Arc::clone(&app_data_ptr);
+        siv.add_global_callback('R', move |siv| on_reconnect(&app_data_ptr, siv));
+    }
+    {
+        let router = router.clone();
+        let config_path = config_path.clone();
+        siv.add_global_callback(cursive::event::Event::CtrlChar('p'), move |siv| {
+            show_command_palette(siv, &router, &config_path)
+        });
+    }
+    watch_config(&siv, &router, config_path);
     // show_resource_picker_dialog(app_data_ptr, &mut siv);
     siv.run();
 }
 
+fn on_sql_console(siv: &mut cursive::Cursive, router: &Router) {
+    // Arbitrary queries would give access to the redacted columns
+    let redacting = {
+        let app_data = router.app_data_ptr.lock().unwrap();
+        !app_data.settings.unmask && app_data.resources.values().any(|r| !r.redact.is_empty())
+    };
+    if redacting {
+        siv.add_layer(views::Dialog::info(
+            "The SQL console is disabled when columns are redacted, unless running with --unmask",
+        ));
+        return;
+    }
+
+    router.push(siv, Box::new(SqlConsoleRoute::default()))
+}
+
+const ROUTES: &str = "routes";
+
+fn add_route_layer(siv: &mut cursive::Cursive, view: impl cursive::view::IntoBoxedView) {
+    siv.call_on_name(ROUTES, |routes: &mut views::StackView| {
+        routes.add_layer(view)
+    });
+}
+
+fn pop_route_layer(siv: &mut cursive::Cursive) {
+    siv.call_on_name(ROUTES, |routes: &mut views::StackView| {
+        routes.pop_layer();
+    });
+}
+
+fn set_status(siv: &mut cursive::Cursive, status: impl Into<StyledString>) {
+    let status = status.into();
+    siv.call_on_name("status", |v: &mut views::TextView| v.set_content(status));
+}
+
+fn update_connection_status(app_data: &AppData) {
+    let mut names: Vec<&String> = app_data.connection_states.keys().collect();
+    names.sort();
+
+    let mut status = StyledString::new();
+    for name in names {
+        let (state, color) = match app_data.connection_states[name] {
+            ConnectionState::Connected => ("connected", cursive::style::BaseColor::Green.dark()),
+            ConnectionState::Unreachable => ("unreachable", cursive::style::BaseColor::Red.dark()),
+            ConnectionState::Disconnected => {
+                ("disconnected", cursive::style::BaseColor::Yellow.dark())
+            }
+        };
+        status.append_plain(format!(" {name}: "));
+        status.append_styled(state, color);
+    }
+    app_data.connection_status.set_content(status);
+}
+
+// Records the state of a database after it was used. The database must have
+// been released: AppData and the databases are never locked together.
+fn set_connection_state(app_data_ptr: &AppDataPtr, name: &str, state: ConnectionState) {
+    let mut app_data = app_data_ptr.lock().unwrap();
+    app_data.connection_states.insert(name.to_owned(), state);
+    update_connection_status(&app_data);
+}
+
+// The database of a connection. AppData is not locked while it's used.
+fn get_database(app_data_ptr: &AppDataPtr, connection: &str) -> Result<DatabasePtr> {
+    let app_data = app_data_ptr.lock().unwrap();
+    let db = app_data
+        .dbs
+        .get(connection)
+        .with_context(|| format!("not connected to {connection}"))?;
+    Ok(Arc::clone(db))
+}
+
+// Reconnects to the databases that don't answer, in the background since
+// reconnecting retries for a few seconds
+fn on_reconnect(app_data_ptr: &AppDataPtr, siv: &mut cursive::Cursive) {
+    let dbs: Vec<(String, DatabasePtr)> = {
+        let app_data = app_data_ptr.lock().unwrap();
+        let mut dbs: Vec<(String, DatabasePtr)> = app_data
+            .dbs
+            .iter()
+            .map(|(name, db)| (name.clone(), Arc::clone(db)))
+            .collect();
+        dbs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        dbs
+    };
+    set_status(siv, "Reconnecting...");
+
+    let app_data_ptr = Arc::clone(app_data_ptr);
+    let cb_sink = siv.cb_sink().clone();
+    std::thread::spawn(move || {
+        let mut errors = Vec::new();
+        for (name, db) in dbs {
+            if db.lock().unwrap().is_healthy() {
+                continue;
+            }
+            if let Err(err) = db::reconnect(&db) {
+                log::error!("{err:#}");
+                errors.push(format!("{err:#}"));
+            }
+            let state = db.lock().unwrap().state();
+            set_connection_state(&app_data_ptr, &name, state);
+        }
+
+        let status = if errors.is_empty() {
+            StyledString::plain("Reconnected")
+        } else {
+            StyledString::styled(errors.join(", "), cursive::style::BaseColor::Red.dark())
+        };
+        // The UI may be gone
+        let _ = cb_sink.send(Box::new(move |siv| set_status(siv, status)));
+    });
+}
+
+type UiUpdate = Box<dyn FnOnce(&mut cursive::Cursive) + Send>;
+
+// The files the configuration is made of are recorded right away when it's
+// valid, for the watcher to watch them
+fn reload_config(config_path: &Path, router: &Router) -> UiUpdate {
+    let (config, diagnostics) = check_config(config_path, None);
+    let errors: Vec<&Diagnostic> = diagnostics.iter().filter(|d| d.is_error()).collect();
+    for diagnostic in &diagnostics {
+        match diagnostic.severity {
+            Severity::Error => log::error!("Configuration error: {diagnostic}"),
+            Severity::Warning => log::warn!("Configuration warning: {diagnostic}"),
+        }
+    }
+
+    match config {
+        Some(config) if errors.is_empty() => {
+            log::info!("configuration reloaded");
+            router.app_data_ptr.lock().unwrap().config_files = config.files;
+            let router = router.clone();
+            let warnings = diagnostics.len();
+            Box::new(move |siv| {
+                let restart_needed = {
+                    let mut app_data = router.app_data_ptr.lock().unwrap();
+                    app_data.resources = config.resources;
+                    app_data.locations = config.locations;
+                    app_data.connections != config.connections
+                        || app_data.audit != config.audit
+                        || app_data
+                            .resources
+                            .values()
+                            .any(|r| !app_data.dbs.contains_key(r.connection_name()))
+                };
+                router.reload(siv);
+
+                let mut status = "Configuration reloaded".to_owned();
+                if warnings > 0 {
+                    write!(status, " with {warnings} warning(s), see the logs").unwrap();
+                }
+                if restart_needed {
+                    log::warn!(
+                        "the connections or the audit settings changed, restart to use them"
+                    );
+                    status.push_str(", restart to apply the changes to the connections and audit");
+                }
+                set_status(siv, status);
+            })
+        }
+        _ => {
+            let mut status = match errors.first() {
+                Some(Diagnostic {
+                    location: Some(location),
+                    message,
+                    ..
+                }) => format!("Configuration error: {location}: {message}"),
+                Some(error) => format!("Configuration error: {}", error.message),
+                None => "Configuration error: the configuration couldn't be loaded".to_owned(),
+            };
+            if errors.len() > 1 {
+                write!(status, " ({} more in the logs)", errors.len() - 1).unwrap();
+            }
+            let status = StyledString::styled(status, cursive::style::BaseColor::Red.dark());
+            Box::new(move |siv| set_status(siv, status))
+        }
+    }
+}
+
+// The files watched are the ones of the last reload, from here or from the
+// command palette
+fn watch_config(siv: &cursive::Cursive, router: &Router, config_path: PathBuf) {
+    let cb_sink = siv.cb_sink().clone();
+    let router = router.clone();
+
+    std::thread::spawn(move || {
+        loop {
+            let mut watched = router.app_data_ptr.lock().unwrap().config_files.clone();
+            watched.push(config_path.clone());
+            if let Err(err) = wait_for_change(&watched) {
+                log::error!("Error watching the configuration files: {err:#}");
+                return;
+            }
+            // Let editors finish writing
+            std::thread::sleep(Duration::from_millis(100));
+
+            let update = reload_config(&config_path, &router);
+            if cb_sink.send(update).is_err() {
+                return; // the UI is gone
+            }
+        }
+    });
+}
+
 fn is_consonnant(c: char) -> bool {
     !matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
 }
 
-fn assign_shortcuts<'a>(strs: impl IntoIterator<Item = &'a str>) -> Vec<Option<(usize, char)>> {
-    let mut assigned: HashSet<char> = HashSet::new();
-    let mut res: Vec<Option<(usize, char)>> = Vec::new();
+// Assigns a key to each label, the pinned one if any, else a letter of the
+// label. The position of the key in the label is returned too, if it's in it.
+fn assign_shortcuts<'a>(
+    items: impl IntoIterator<Item = (&'a str, Option<char>)>,
+) -> Vec<Option<(Option<usize>, char)>> {
+    let items: Vec<(&str, Option<char>)> = items.into_iter().collect();
+    // Pinned and reserved keys are never given to other labels
+    let mut assigned: HashSet<char> = items
+        .iter()
+        .filter_map(|(_, pinned)| *pinned)
+        .chain(RESERVED_SHORTCUTS)
+        .collect();
+    let mut res: Vec<Option<(Option<usize>, char)>> = Vec::new();
+
+    'outer: for (s, pinned) in items {
+        if let Some(pinned) = pinned {
+            let idx = s
+                .chars()
+                .position(|c| c.to_lowercase().eq(pinned.to_lowercase()));
+            res.push(Some((idx, pinned)));
+            continue;
+        }
 
-    'outer: for s in strs {
         let mut is_prev_alphabetic = false;
         let word_starts = s.chars().enumerate().filter(|(_, c)| {
             let is_alphabetic = c.is_alphabetic();
@@ -60,7 +394,7 @@ fn assign_shortcuts<'a>(strs: impl IntoIterator<Item = &'a str>) -> Vec<Option<(
             }
 
             assigned.insert(c);
-            res.push(Some((idx, c)));
+            res.push(Some((Some(idx), c)));
             continue 'outer;
         }
 
@@ -70,26 +404,62 @@ fn assign_shortcuts<'a>(strs: impl IntoIterator<Item = &'a str>) -> Vec<Option<(
     res
 }
 
-fn build_shortcut_select_view<T: 'static + Send + Sync + Clone>(
-    mut v: views::SelectView<T>,
-    name: &str,
-) -> impl cursive::view::View {
-    let shortcuts = assign_shortcuts(v.iter().map(|(label, _)| label));
+fn highlight_shortcuts<T: 'static + Send + Sync + Clone>(
+    v: &mut views::SelectView<T>,
+    pinned: &[Option<char>],
+) -> Vec<Option<(Option<usize>, char)>> {
+    let shortcuts = assign_shortcuts(
+        v.iter()
+            .zip(pinned.iter().copied().chain(std::iter::repeat(None)))
+            .map(|((label, _), pinned)| (label, pinned)),
+    );
 
+    let style = Into::<cursive::style::Style>::into(cursive::style::Effect::Bold)
+        .combine(cursive::style::PaletteColor::Highlight);
     for ((label, _), shortcut) in v.iter_mut().zip(shortcuts.iter()) {
-        let Some((idx, _)) = shortcut else {
+        let Some((idx, c)) = shortcut else {
             continue;
         };
         let txt = label.source().to_owned();
         label.remove_spans(0..label.spans_raw().len());
-        label.append_plain(String::from_iter(txt.chars().take(*idx)));
-        label.append_styled(
-            String::from_iter(txt.chars().skip(*idx).take(1)),
-            Into::<cursive::style::Style>::into(cursive::style::Effect::Bold)
-                .combine(cursive::style::PaletteColor::Highlight),
-        );
-        label.append_plain(String::from_iter(txt.chars().skip(idx + 1)));
+        match idx {
+            Some(idx) => {
+                label.append_plain(String::from_iter(txt.chars().take(*idx)));
+                label.append_styled(String::from_iter(txt.chars().skip(*idx).take(1)), style);
+                label.append_plain(String::from_iter(txt.chars().skip(idx + 1)));
+            }
+            // Pinned keys that are not in the label are shown after it
+            None => {
+                label.append_plain(format!("{txt} ["));
+                label.append_styled(c.to_string(), style);
+                label.append_plain("]");
+            }
+        }
+    }
+
+    shortcuts
+}
+
+fn submit_selection(siv: &mut cursive::Cursive, name: &str) {
+    if let Some(Some(cb)) = siv.call_on_name(name, |v: &mut views::SelectView| {
+        if let cursive::event::EventResult::Consumed(Some(cb)) =
+            v.on_event(cursive::event::Event::Key(cursive::event::Key::Enter))
+        {
+            Some(cb.clone())
+        } else {
+            None
+        }
+    }) {
+        cb(siv);
     }
+}
+
+fn build_shortcut_select_view<T: 'static + Send + Sync + Clone>(
+    mut v: views::SelectView<T>,
+    name: &str,
+    pinned: Vec<Option<char>>,
+) -> impl cursive::view::View {
+    let shortcuts = highlight_shortcuts(&mut v, &pinned);
 
     let mut res = views::OnEventView::new(v.with_name(name));
 
@@ -99,17 +469,79 @@ fn build_shortcut_select_view<T: 'static + Send + Sync + Clone>(
         };
         let name = name.to_owned();
         res.set_on_event(cursive::event::Event::Char(*c), move |s| {
-            if let Some(Some(cb)) = s.call_on_name(&name, |v: &mut views::SelectView| {
-                v.set_selection(idx);
-                if let cursive::event::EventResult::Consumed(Some(cb)) =
-                    v.on_event(cursive::event::Event::Key(cursive::event::Key::Enter))
-                {
-                    Some(cb.clone())
+            s.call_on_name(&name, |v: &mut views::SelectView<T>| v.set_selection(idx));
+            submit_selection(s, &name);
+        });
+    }
+
+    res
+}
+
+// Labels of picker items computed from their values, for labels that change
+// while the picker is shown
+type Labeler = Arc<dyn Fn(&str) -> String + Send + Sync>;
+
+const FILTER_WIDTH: usize = 20;
+
+// Up and Down move in the list while typing in the filter box
+fn build_filtered_select_view(
+    v: views::SelectView,
+    name: &str,
+    labeler: Option<Labeler>,
+) -> impl cursive::view::View {
+    let items: Vec<(String, String)> = v
+        .iter()
+        .map(|(label, value)| (label.to_owned(), value.clone()))
+        .collect();
+
+    let filter = {
+        let name = name.to_owned();
+        views::EditView::new().on_edit(move |siv, text, _| {
+            let cb = siv.call_on_name(&name, |v: &mut views::SelectView| {
+                let labels: Vec<String> = items
+                    .iter()
+                    .map(|(label, value)| {
+                        labeler.as_ref().map_or_else(|| label.clone(), |f| f(value))
+                    })
+                    .collect();
+                v.clear();
+                for idx in fuzzy::rank(text, labels.iter().map(String::as_str)) {
+                    v.add_item(labels[idx].clone(), items[idx].1.clone());
+                }
+                v.set_selection(0)
+            });
+            if let Some(cb) = cb {
+                cb(siv);
+            }
+        })
+    };
+    let filter = {
+        let name = name.to_owned();
+        filter.on_submit(move |siv, _| submit_selection(siv, &name))
+    };
+
+    let mut res = views::OnEventView::new(
+        views::LinearLayout::vertical()
+            .child(filter.min_width(FILTER_WIDTH))
+            .child(v.with_name(name).scrollable()),
+    );
+    for (key, up, rows) in [
+        (cursive::event::Key::Up, true, 1),
+        (cursive::event::Key::Down, false, 1),
+        (cursive::event::Key::PageUp, true, 10),
+        (cursive::event::Key::PageDown, false, 10),
+    ] {
+        let name = name.to_owned();
+        res.set_on_pre_event(key, move |siv| {
+            let cb = siv.call_on_name(&name, |v: &mut views::SelectView| {
+                if up {
+                    v.select_up(rows)
                 } else {
-                    None
+                    v.select_down(rows)
                 }
-            }) {
-                cb(s);
+            });
+            if let Some(cb) = cb {
+                cb(siv);
             }
         });
     }
@@ -117,6 +549,96 @@ fn build_shortcut_select_view<T: 'static + Send + Sync + Clone>(
     res
 }
 
+fn uses_filter(mode: PickerMode, v: &views::SelectView, pinned: &[Option<char>]) -> bool {
+    match mode {
+        PickerMode::Shortcuts => false,
+        PickerMode::Filter => true,
+        PickerMode::Auto => assign_shortcuts(
+            v.iter()
+                .zip(pinned.iter().copied().chain(std::iter::repeat(None)))
+                .map(|((label, _), pinned)| (label, pinned)),
+        )
+        .contains(&None),
+    }
+}
+
+const DESCRIPTION_WIDTH: usize = 60;
+
+// Picker showing the description of the highlighted item under the list, and
+// its details in a dialog with ?. Items are described by their value, and
+// labeled by the labeler when filtered, if any.
+fn build_described_picker(
+    mut select_view: views::SelectView,
+    name: &str,
+    pinned: Vec<Option<char>>,
+    descriptions: HashMap<String, String>,
+    details: impl Fn(&str) -> Option<(String, String)> + Send + Sync + 'static,
+    mode: PickerMode,
+    labeler: Option<Labeler>,
+) -> impl cursive::view::View {
+    let filter = uses_filter(mode, &select_view, &pinned);
+    let description_name = format!("{name}_description");
+    let description = select_view
+        .selection()
+        .and_then(|value| descriptions.get(value.as_str()).cloned())
+        .unwrap_or_default();
+    let has_descriptions = !descriptions.is_empty();
+
+    {
+        let description_name = description_name.clone();
+        select_view.set_on_select(move |siv, value: &String| {
+            let description = descriptions.get(value).cloned().unwrap_or_default();
+            siv.call_on_name(&description_name, |v: &mut views::TextView| {
+                v.set_content(description)
+            });
+        });
+    }
+
+    let mut layout = views::LinearLayout::vertical();
+    if filter {
+        layout.add_child(build_filtered_select_view(select_view, name, labeler));
+    } else {
+        layout.add_child(build_shortcut_select_view(select_view, name, pinned));
+    }
+    if has_descriptions {
+        layout.add_child(
+            views::Panel::new(
+                views::TextView::new(description)
+                    .with_name(description_name)
+                    .max_width(DESCRIPTION_WIDTH),
+            )
+            .title("Description"),
+        );
+    }
+
+    let name = name.to_owned();
+    // Before the filter box, which would take it
+    views::OnEventView::new(layout).on_pre_event('?', move |siv| {
+        let value = siv
+            .call_on_name(&name, |v: &mut views::SelectView| v.selection())
+            .flatten();
+        if let Some((title, text)) = value.and_then(|value| details(&value)) {
+            show_help(siv, title, text);
+        }
+    })
+}
+
+fn show_help(siv: &mut cursive::Cursive, title: String, text: String) {
+    siv.add_layer(
+        views::OnEventView::new(
+            views::Dialog::around(views::ScrollView::new(views::TextView::new(text)))
+                .title(title)
+                .dismiss_button("Close"),
+        )
+        .on_event(cursive::event::Key::Esc, |siv| {
+            siv.pop_layer();
+        })
+        .on_event('?', |siv| {
+            siv.pop_layer();
+        }),
+    );
+}
+
 fn get_resource(app_data_ptr: &AppDataPtr, resource_id: &str) -> Resource {
     let app_data = app_data_ptr.lock().unwrap();
     app_data
@@ -126,9 +648,190 @@ fn get_resource(app_data_ptr: &AppDataPtr, resource_id: &str) -> Resource {
         .clone()
 }
 
+fn sorted_links(r: &Resource) -> Vec<(&String, &Link)> {
+    let mut links: Vec<(&String, &Link)> = r.links.iter().collect();
+    links.sort_by_key(|(_, link)| picker_order(link.order, link.position));
+    links
+}
+
+struct Action {
+    label: String,
+    key: Option<&'static str>,
+    run: Arc<dyn Fn(&mut cursive::Cursive) + Send + Sync>,
+}
+
+impl Action {
+    fn new(
+        label: impl Into<String>,
+        run: impl Fn(&mut cursive::Cursive) + Send + Sync + 'static,
+    ) -> Action {
+        Action {
+            label: label.into(),
+            key: None,
+            run: Arc::new(run),
+        }
+    }
+
+    // Action of a key of the route's view, run by sending the key to it
+    fn key(
+        label: impl Into<String>,
+        key: &'static str,
+        event: impl Into<cursive::event::Event>,
+    ) -> Action {
+        let event = event.into();
+        Action {
+            label: label.into(),
+            key: Some(key),
+            run: Arc::new(move |siv| siv.on_event(event.clone())),
+        }
+    }
+
+    fn with_key(self, key: &'static str) -> Action {
+        Action {
+            key: Some(key),
+            ..self
+        }
+    }
+
+    fn palette_label(&self) -> String {
+        match self.key {
+            Some(key) => format!("{} ({key})", &self.label),
+            None => self.label.clone(),
+        }
+    }
+}
+
+fn global_actions(router: &Router, config_path: &Path) -> Vec<Action> {
+    let mut actions = vec![
+        {
+            let router = router.clone();
+            Action::new("Go back", move |siv| router.pop(siv)).with_key("Esc")
+        },
+        {
+            let router = router.clone();
+            Action::new("Open the SQL console", move |siv| {
+                on_sql_console(siv, &router)
+            })
+            .with_key(":")
+        },
+        {
+            let router = router.clone();
+            Action::new("Show the logs", move |siv| {
+                router.push(siv, Box::new(LogRoute {}))
+            })
+            .with_key("~")
+        },
+        {
+            let app_data_ptr = Arc::clone(&router.app_data_ptr);
+            Action::new("Reconnect to the databases", move |siv| {
+                on_reconnect(&app_data_ptr, siv)
+            })
+            .with_key("R")
+        },
+        {
+            let router = router.clone();
+            let config_path = config_path.to_owned();
+            Action::new("Reload the configuration", move |siv| {
+                let update = reload_config(&config_path, &router);
+                update(siv);
+            })
+        },
+        Action::new("Quit", |siv| siv.quit()).with_key("q"),
+    ];
+
+    let app_data = router.app_data_ptr.lock().unwrap();
+    let mut resources: Vec<(&String, &Resource)> = app_data.resources.iter().collect();
+    resources.sort_by_key(|(_, r)| picker_order(r.order, r.position));
+    for (resource_id, r) in resources {
+        let mut searches: Vec<(&String, &Search)> = r.search.iter().collect();
+        searches.sort_by_key(|(_, s)| picker_order(s.order, s.position));
+        for (search_id, _) in searches {
+            let router = router.clone();
+            let resource_id = resource_id.clone();
+            let search_id = search_id.clone();
+            actions.push(Action::new(
+                format!("Search {} by {search_id}", &r.name),
+                move |siv| {
+                    on_pick_search(
+                        Arc::clone(&router.app_data_ptr),
+                        siv,
+                        &router,
+                        &resource_id,
+                        &search_id,
+                    )
+                },
+            ));
+        }
+    }
+
+    actions
+}
+
+// Closes the dialogs shown over the routes, whose layer is the first one
+fn close_dialogs(siv: &mut cursive::Cursive) {
+    while siv.screen().len() > 1 {
+        siv.pop_layer();
+    }
+}
+
+fn show_command_palette(siv: &mut cursive::Cursive, router: &Router, config_path: &Path) {
+    if siv
+        .find_name::<views::SelectView>("command_palette")
+        .is_some()
+    {
+        return;
+    }
+
+    let mut actions = router.actions(siv);
+    actions.extend(global_actions(router, config_path));
+
+    // Items are the indexes of the actions
+    let mut select_view = views::SelectView::new();
+    let mut width = 0;
+    for (idx, action) in actions.iter().enumerate() {
+        let label = action.palette_label();
+        width = width.max(label.chars().count());
+        select_view.add_item(label, idx.to_string());
+    }
+    select_view.set_on_submit(move |siv, idx: &String| {
+        let action = &actions[idx.parse::<usize>().expect("invalid action index")];
+        let run = Arc::clone(&action.run);
+        close_dialogs(siv);
+        run(siv);
+    });
+
+    siv.add_layer(
+        views::OnEventView::new(
+            // Keeps its width while filtering
+            views::Dialog::around(
+                build_filtered_select_view(select_view, "command_palette", None).min_width(width),
+            )
+            .title("Commands"),
+        )
+        .on_event(cursive::event::Key::Esc, |siv| {
+            siv.pop_layer();
+        }),
+    );
+}
+
 trait Route {
     fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router);
     fn unmount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router);
+
+    // Whether the route can still be mounted with these resources, after the
+    // configuration was reloaded
+    fn is_valid(&self, _resources: &HashMap<String, Resource>) -> bool {
+        true
+    }
+
+    fn actions(
+        &self,
+        _app_data_ptr: &AppDataPtr,
+        _siv: &mut cursive::Cursive,
+        _router: &Router,
+    ) -> Vec<Action> {
+        Vec::new()
+    }
 }
 
 struct RouterContextData {
@@ -159,6 +862,45 @@ impl Router {
         ctx.history.push(route);
     }
 
+    fn remount(&self, siv: &mut cursive::Cursive) {
+        let ctx = self.data.lock().unwrap();
+        if let Some(route) = ctx.history.last() {
+            route.unmount(Arc::clone(&self.app_data_ptr), siv, &self.clone());
+            route.mount(Arc::clone(&self.app_data_ptr), siv, &self.clone());
+        }
+    }
+
+    // Remounts the current route after the resources were reloaded, dropping the
+    // routes using resources or searches that don't exist anymore
+    fn reload(&self, siv: &mut cursive::Cursive) {
+        let mut ctx = self.data.lock().unwrap();
+        let Some(route) = ctx.history.last() else {
+            return;
+        };
+        route.unmount(Arc::clone(&self.app_data_ptr), siv, &self.clone());
+
+        let valid_routes = {
+            let app_data = self.app_data_ptr.lock().unwrap();
+            ctx.history
+                .iter()
+                .position(|route| !route.is_valid(&app_data.resources))
+                .unwrap_or(ctx.history.len())
+        };
+        ctx.history.truncate(valid_routes);
+
+        if let Some(route) = ctx.history.last() {
+            route.mount(Arc::clone(&self.app_data_ptr), siv, &self.clone());
+        }
+    }
+
+    fn actions(&self, siv: &mut cursive::Cursive) -> Vec<Action> {
+        let ctx = self.data.lock().unwrap();
+        ctx.history
+            .last()
+            .map(|route| route.actions(&self.app_data_ptr, siv, self))
+            .unwrap_or_default()
+    }
+
     fn pop(&self, siv: &mut cursive::Cursive) {
         let mut ctx = self.data.lock().unwrap();
         if let Some(route) = ctx.history.pop() {
@@ -186,30 +928,63 @@ struct RouteResourcePicker {}
 impl Route for RouteResourcePicker {
     fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
         let router = router.clone();
-        siv.add_layer(views::Dialog::around(
-            views::OnEventView::new(build_resource_picker(Arc::clone(&app_data_ptr), &router))
-                .on_event(cursive::event::Key::Esc, move |siv| {
-                    router.pop(siv);
-                }),
-        ));
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(build_resource_picker(Arc::clone(&app_data_ptr), &router))
+                    .on_event(cursive::event::Key::Esc, move |siv| {
+                        router.pop(siv);
+                    }),
+            ),
+        );
     }
 
     fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
-        siv.pop_layer();
+        pop_route_layer(siv);
+    }
+
+    fn actions(
+        &self,
+        _app_data_ptr: &AppDataPtr,
+        _siv: &mut cursive::Cursive,
+        _router: &Router,
+    ) -> Vec<Action> {
+        vec![Action::key(
+            "Show the details of the highlighted item",
+            "?",
+            '?',
+        )]
     }
 }
 
 fn build_resource_picker(app_data_ptr: AppDataPtr, router: &Router) -> impl cursive::view::View {
     let mut select_view = views::SelectView::new();
-    {
+    let mut pinned = Vec::new();
+    let mut descriptions = HashMap::new();
+    let mode = {
         let app_data = app_data_ptr.lock().unwrap();
 
-        for (k, v) in &app_data.resources {
+        let mut resources: Vec<(&String, &Resource)> = app_data.resources.iter().collect();
+        resources.sort_by_key(|(_, r)| picker_order(r.order, r.position));
+        for (k, v) in resources {
             select_view.add_item(v.name.as_str(), k.to_owned());
+            pinned.push(v.shortcut);
+            if let Some(description) = &v.description {
+                descriptions.insert(k.to_owned(), description.clone());
+            }
+        }
+        app_data.settings.picker_mode
+    };
+
+    let details = {
+        let app_data_ptr = Arc::clone(&app_data_ptr);
+        move |resource_id: &str| {
+            let app_data = app_data_ptr.lock().unwrap();
+            let r = app_data.resources.get(resource_id)?;
+            Some((r.name.clone(), resource_help(r)))
         }
     };
 
-    select_view.sort_by_label();
     let router = router.clone();
     select_view.set_on_submit(move |siv, resource_id: &str| {
         router.push(
@@ -222,7 +997,15 @@ fn build_resource_picker(app_data_ptr: AppDataPtr, router: &Router) -> impl curs
 
     views::LinearLayout::vertical()
         .child(views::TextView::new("Resources"))
-        .child(build_shortcut_select_view(select_view, "resource_picker"))
+        .child(build_described_picker(
+            select_view,
+            "resource_picker",
+            pinned,
+            descriptions,
+            details,
+            mode,
+            None,
+        ))
 }
 
 struct SearchPickerRoute {
@@ -232,24 +1015,44 @@ struct SearchPickerRoute {
 impl Route for SearchPickerRoute {
     fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
         let router = router.clone();
-        siv.add_layer(views::Dialog::around(
-            views::OnEventView::new(build_search_picker(
-                Arc::clone(&app_data_ptr),
-                &router,
-                &self.resource_id,
-            ))
-            .on_event(cursive::event::Key::Esc, move |siv| {
-                router.pop(siv);
-            }),
-        ));
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(build_search_picker(
+                    Arc::clone(&app_data_ptr),
+                    &router,
+                    &self.resource_id,
+                ))
+                .on_event(cursive::event::Key::Esc, move |siv| {
+                    router.pop(siv);
+                }),
+            ),
+        );
     }
 
     fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
-        siv.pop_layer();
+        pop_route_layer(siv);
     }
-}
 
-fn build_search_picker(
+    fn is_valid(&self, resources: &HashMap<String, Resource>) -> bool {
+        resources.contains_key(&self.resource_id)
+    }
+
+    fn actions(
+        &self,
+        _app_data_ptr: &AppDataPtr,
+        _siv: &mut cursive::Cursive,
+        _router: &Router,
+    ) -> Vec<Action> {
+        vec![Action::key(
+            "Show the details of the highlighted item",
+            "?",
+            '?',
+        )]
+    }
+}
+
+fn build_search_picker(
     app_data_ptr: AppDataPtr,
     router: &Router,
     resource_id: &str,
@@ -258,36 +1061,36 @@ fn build_search_picker(
 
     let r = get_resource(&app_data_ptr, resource_id);
 
-    for search in r.search.keys() {
-        select_view.add_item_str(search);
+    let mut searches: Vec<(&String, &Search)> = r.search.iter().collect();
+    searches.sort_by_key(|(_, s)| picker_order(s.order, s.position));
+    for (search_id, _) in &searches {
+        select_view.add_item_str(*search_id);
     }
-
-    select_view.sort_by_label();
+    let pinned: Vec<Option<char>> = searches.iter().map(|(_, s)| s.shortcut).collect();
+    let descriptions: HashMap<String, String> = searches
+        .iter()
+        .filter_map(|(search_id, s)| Some(((*search_id).clone(), s.description.clone()?)))
+        .collect();
+    let details = {
+        let r = r.clone();
+        move |search_id: &str| {
+            let search = r.search.get(search_id)?;
+            Some((format!("Search {search_id}"), search_help(search)))
+        }
+    };
+    let mode = app_data_ptr.lock().unwrap().settings.picker_mode;
 
     {
         let resource_id = resource_id.to_owned();
         let router = router.clone();
         select_view.set_on_submit(move |siv, search_id: &str| {
-            let r = get_resource(&app_data_ptr, &resource_id);
-            let s = r.search.get(search_id).expect("invalid search id");
-
-            if s.params.is_empty() {
-                on_query(
-                    Arc::clone(&app_data_ptr),
-                    siv,
-                    &router,
-                    &resource_id,
-                    search_id,
-                );
-            } else {
-                router.push(
-                    siv,
-                    Box::new(QueryRoute {
-                        resource_id: resource_id.clone(),
-                        search_id: search_id.to_owned(),
-                    }),
-                );
-            }
+            on_pick_search(
+                Arc::clone(&app_data_ptr),
+                siv,
+                &router,
+                &resource_id,
+                search_id,
+            )
         });
     }
 
@@ -295,33 +1098,80 @@ fn build_search_picker(
 
     views::LinearLayout::vertical()
         .child(views::TextView::new(&title))
-        .child(build_shortcut_select_view(select_view, "search_picker"))
+        .child(build_described_picker(
+            select_view,
+            "search_picker",
+            pinned,
+            descriptions,
+            details,
+            mode,
+            None,
+        ))
+}
+
+fn on_pick_search(
+    app_data_ptr: AppDataPtr,
+    siv: &mut cursive::Cursive,
+    router: &Router,
+    resource_id: &str,
+    search_id: &str,
+) {
+    let r = get_resource(&app_data_ptr, resource_id);
+    let s = r.search.get(search_id).expect("invalid search id");
+
+    if s.params.is_empty() {
+        on_query(app_data_ptr, siv, router, resource_id, search_id, &[]);
+    } else {
+        router.push(
+            siv,
+            Box::new(QueryRoute {
+                resource_id: resource_id.to_owned(),
+                search_id: search_id.to_owned(),
+                initial_values: Vec::new(),
+                hidden: Vec::new(),
+            }),
+        );
+    }
 }
 
 struct QueryRoute {
     resource_id: String,
     search_id: String,
+    initial_values: Vec<String>,
+    // Initial values bound from redacted columns, masked and not editable
+    hidden: Vec<bool>,
 }
 
 impl Route for QueryRoute {
     fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
         let router = router.clone();
 
-        siv.add_layer(views::Dialog::around(
-            views::OnEventView::new(build_query(
-                Arc::clone(&app_data_ptr),
-                &router,
-                &self.resource_id,
-                &self.search_id,
-            ))
-            .on_event(cursive::event::Key::Esc, move |siv| {
-                router.pop(siv);
-            }),
-        ));
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(build_query(
+                    Arc::clone(&app_data_ptr),
+                    &router,
+                    &self.resource_id,
+                    &self.search_id,
+                    &self.initial_values,
+                    &self.hidden,
+                ))
+                .on_event(cursive::event::Key::Esc, move |siv| {
+                    router.pop(siv);
+                }),
+            ),
+        );
     }
 
     fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
-        siv.pop_layer();
+        pop_route_layer(siv);
+    }
+
+    fn is_valid(&self, resources: &HashMap<String, Resource>) -> bool {
+        resources
+            .get(&self.resource_id)
+            .is_some_and(|r| r.search.contains_key(&self.search_id))
     }
 }
 
@@ -330,16 +1180,54 @@ fn build_query(
     router: &Router,
     resource_id: &str,
     search_id: &str,
+    initial_values: &[String],
+    hidden: &[bool],
 ) -> impl cursive::view::View {
     let r = get_resource(&app_data_ptr, resource_id);
     let s = r.search.get(search_id).expect("invalid search id");
 
     let title = format!("Search {} by {}", &r.name, search_id);
     let mut layout = views::LinearLayout::vertical().child(views::TextView::new(&title));
+    if let Some(description) = &s.description {
+        layout.add_child(
+            views::TextView::new(StyledString::styled(
+                description.trim(),
+                cursive::style::Effect::Italic,
+            ))
+            .max_width(DESCRIPTION_WIDTH),
+        );
+    }
 
-    for param in &s.params {
-        let input = views::EditView::new().with_name(&param.name);
-        layout.add_child(views::Panel::new(input).title(&param.name));
+    let mut hidden_values: Vec<Option<String>> = Vec::new();
+    for (idx, param) in s.params.iter().enumerate() {
+        let mut input = views::EditView::new();
+        let value = initial_values.get(idx);
+        if hidden.get(idx).copied().unwrap_or(false) {
+            input.set_content(MASK);
+            input.disable();
+            hidden_values.push(value.cloned());
+        } else {
+            if let Some(value) = value {
+                input.set_content(value.as_str());
+            }
+            hidden_values.push(None);
+        }
+        let input = input.with_name(&param.name);
+        match &param.description {
+            Some(description) => layout.add_child(
+                views::Panel::new(
+                    views::LinearLayout::vertical().child(input).child(
+                        views::TextView::new(StyledString::styled(
+                            description.trim(),
+                            cursive::style::Effect::Italic,
+                        ))
+                        .max_width(DESCRIPTION_WIDTH),
+                    ),
+                )
+                .title(&param.name),
+            ),
+            None => layout.add_child(views::Panel::new(input).title(&param.name)),
+        }
     }
 
     {
@@ -354,6 +1242,7 @@ fn build_query(
                 &router,
                 &resource_id,
                 &search_id,
+                &hidden_values,
             )
         }));
     }
@@ -378,11 +1267,12 @@ fn on_query_helper(
     resource_id: &str,
     search_id: &str,
     params_str_values: &[String],
-) -> Result<(String, Vec<postgres::Row>)> {
+    hidden: &[bool],
+) -> Result<(String, Vec<postgres::Row>, QueryInfo)> {
     let r = get_resource(&app_data_ptr, resource_id);
     let s = r.search.get(search_id).expect("invalid search id");
     let mut title = String::new();
-    let mut param_values: Vec<Box<dyn postgres::types::ToSql + Sync>> = Vec::new();
+    let mut param_values: Vec<SqlValue> = Vec::new();
 
     write!(&mut title, "{} / {} (", &r.name, search_id)?;
 
@@ -391,7 +1281,11 @@ fn on_query_helper(
             write!(&mut title, ", ")?;
         }
 
-        write!(&mut title, "{}={}", &param.name, &str_val)?;
+        if hidden.get(idx).copied().unwrap_or(false) {
+            write!(&mut title, "{}={MASK}", &param.name)?;
+        } else {
+            write!(&mut title, "{}={}", &param.name, &str_val)?;
+        }
 
         param_values.push(
             sql_value_from_string(str_val, param.ty.clone().unwrap_or(SearchParamType::Text))
@@ -401,16 +1295,203 @@ fn on_query_helper(
 
     write!(&mut title, ")")?;
 
-    let param_values_ref: Vec<&(dyn postgres::types::ToSql + Sync)> =
-        param_values.iter().map(|v| v.as_ref()).collect();
+    let (rows, mut info) = run_query(&app_data_ptr, r.connection_name(), &s.query, param_values)?;
+    info.hidden_params = hidden.to_vec();
 
-    let mut app_data = app_data_ptr.lock().unwrap();
-    let rows = app_data
-        .db
-        .query(&s.query, &param_values_ref)
+    let mut entry = AuditEntry::new(resource_id);
+    entry.search = Some(search_id.to_owned());
+    for (param, str_val) in s.params.iter().zip(params_str_values.iter()) {
+        entry.add_param(&param.name, Some(param), std::slice::from_ref(str_val));
+    }
+    entry.rows = rows.len();
+    record_audit(&app_data_ptr, entry)?;
+
+    Ok((title, rows, info))
+}
+
+#[derive(Clone)]
+struct QueryInfo {
+    connection: String,
+    sql: String,
+    param_types: Vec<postgres::types::Type>,
+    params: Arc<Vec<SqlValue>>,
+    // Parameters bound from redacted columns, whose values are not shown
+    hidden_params: Vec<bool>,
+    duration: Duration,
+    row_count: usize,
+}
+
+fn run_query(
+    app_data_ptr: &AppDataPtr,
+    connection: &str,
+    query: &str,
+    param_values: Vec<SqlValue>,
+) -> Result<(Vec<postgres::Row>, QueryInfo)> {
+    let (rows, param_types, duration) =
+        execute_query(app_data_ptr, connection, query, &param_values)?;
+
+    let info = QueryInfo {
+        connection: connection.to_owned(),
+        sql: query.to_owned(),
+        param_types,
+        params: Arc::new(param_values),
+        hidden_params: Vec::new(),
+        duration,
+        row_count: rows.len(),
+    };
+
+    Ok((rows, info))
+}
+
+fn execute_query(
+    app_data_ptr: &AppDataPtr,
+    connection: &str,
+    query: &str,
+    param_values: &[SqlValue],
+) -> Result<(Vec<postgres::Row>, Vec<postgres::types::Type>, Duration)> {
+    let statement_timeout = app_data_ptr.lock().unwrap().settings.statement_timeout;
+    let db = get_database(app_data_ptr, connection)?;
+
+    let res = db::run_read_only(&db, |client| {
+        execute_query_on(client, statement_timeout, connection, query, param_values)
+    });
+    let state = db.lock().unwrap().state();
+
+    set_connection_state(app_data_ptr, connection, state);
+    res
+}
+
+fn execute_query_on(
+    client: &mut postgres::Client,
+    statement_timeout: Duration,
+    connection: &str,
+    query: &str,
+    param_values: &[SqlValue],
+) -> Result<(Vec<postgres::Row>, Vec<postgres::types::Type>, Duration)> {
+    let param_values_ref: Vec<&(dyn postgres::types::ToSql + Sync)> = param_values
+        .iter()
+        .map(|v| v.as_ref() as &(dyn postgres::types::ToSql + Sync))
+        .collect();
+
+    // Queries run in a read only transaction, so that the SQL console can't
+    // modify the database either
+    let mut transaction = client
+        .build_transaction()
+        .read_only(true)
+        .start()
+        .context("error starting transaction")?;
+    transaction
+        .batch_execute(&format!(
+            "SET LOCAL statement_timeout = {}",
+            statement_timeout.as_millis()
+        ))
+        .context("error setting statement timeout")?;
+    let statement = transaction
+        .prepare(query)
+        .context("error preparing SQL query")?;
+    // Parameters can be bound from redacted columns or be sensitive, only
+    // their number is logged
+    log::debug!(
+        "running query on {connection} with {} parameter(s): {query}",
+        param_values.len()
+    );
+    let start = Instant::now();
+    let rows = transaction
+        .query(&statement, &param_values_ref)
         .context("error running SQL query")?;
+    let duration = start.elapsed();
+    log::info!(
+        "query on {connection} returned {} rows in {duration:?}: {query}",
+        rows.len()
+    );
+    transaction
+        .commit()
+        .context("error committing transaction")?;
+
+    Ok((rows, statement.params().to_vec(), duration))
+}
+
+// Records a query in the audit trail, if enabled. Results must not be shown
+// when this fails.
+fn record_audit(app_data_ptr: &AppDataPtr, mut entry: AuditEntry) -> Result<()> {
+    let (audit, dbs) = {
+        let app_data = app_data_ptr.lock().unwrap();
+        let Some(audit) = &app_data.audit else {
+            return Ok(());
+        };
+        entry.unmasked = app_data.settings.unmask;
+        (audit.clone(), app_data.dbs.clone())
+    };
+
+    let res = crate::audit::record(&audit, &dbs, &entry).context("error writing the audit trail");
+    if let Some(connection) = &audit.connection
+        && let Some(db) = dbs.get(connection)
+    {
+        let state = db.lock().unwrap().state();
+        set_connection_state(app_data_ptr, connection, state);
+    }
+    res
+}
+
+fn record_link_audit(
+    app_data_ptr: &AppDataPtr,
+    resource_id: &str,
+    link_name: &str,
+    rows: &[&ResultRow],
+    result_count: usize,
+    count_only: bool,
+) -> Result<()> {
+    if app_data_ptr.lock().unwrap().audit.is_none() {
+        return Ok(());
+    }
+
+    let r = get_resource(app_data_ptr, resource_id);
+    let link = r.links.get(link_name).expect("invalid link name");
+    let target_resource = get_resource(app_data_ptr, &link.kind);
+    let link_search = target_resource
+        .search
+        .get(&link.search)
+        .expect("invalid link search name");
+
+    let mut entry = AuditEntry::new(resource_id);
+    entry.link = Some(link_name.to_owned());
+    entry.target = Some(link.kind.clone());
+    entry.search = Some(link.search.clone());
+    for (idx, (param, target_param)) in link
+        .search_params
+        .iter()
+        .zip(link_search.params.iter())
+        .enumerate()
+    {
+        let mut values = Vec::new();
+        for row in rows {
+            values.extend(
+                link_param_strings(row, param)
+                    .with_context(|| format!("error binding search parameter {idx}"))?,
+            );
+        }
+        entry.add_param(&target_param.name, Some(target_param), &values);
+    }
+    entry.rows = result_count;
+    entry.count_only = count_only;
+
+    record_audit(app_data_ptr, entry)
+}
+
+fn query_param_types(
+    app_data_ptr: &AppDataPtr,
+    connection: &str,
+    query: &str,
+) -> Result<Vec<postgres::types::Type>> {
+    let db = get_database(app_data_ptr, connection)?;
+
+    let res = db::run_read_only(&db, |client| {
+        client.prepare(query).context("error preparing SQL query")
+    });
+    let state = db.lock().unwrap().state();
 
-    Ok((title, rows))
+    set_connection_state(app_data_ptr, connection, state);
+    Ok(res?.params().to_vec())
 }
 
 fn on_query(
@@ -419,231 +1500,1687 @@ fn on_query(
     router: &Router,
     resource_id: &str,
     search_id: &str,
+    hidden_values: &[Option<String>],
 ) {
     let r = get_resource(&app_data_ptr, resource_id);
     let s = r.search.get(search_id).expect("invalid search id");
     let param_names: Vec<&str> = s.params.iter().map(|p| p.name.as_str()).collect();
 
+    let values = with_hidden_values(
+        gather_query_parameter_strings(siv, param_names.as_slice()),
+        hidden_values,
+    );
+    let hidden: Vec<bool> = hidden_values.iter().map(Option::is_some).collect();
+
     match on_query_helper(
         Arc::clone(&app_data_ptr),
         resource_id,
         search_id,
-        gather_query_parameter_strings(siv, param_names.as_slice()).as_slice(),
+        &values,
+        &hidden,
     ) {
-        Ok((title, rows)) => {
+        Ok((title, rows, info)) => {
             router.push(
                 siv,
                 Box::new(QueryResultsRoute {
                     resource_id: resource_id.to_owned(),
                     title,
                     rows,
+                    row_sources: Vec::new(),
+                    queries: vec![info],
+                    selection: Default::default(),
+                    show_hidden: Default::default(),
                 }),
             );
         }
         Err(err) => {
-            eprintln!("Error running query: {err:?}");
+            log::error!("Error running query: {err:#}");
             siv.add_layer(views::Dialog::around(build_query_error(&err)));
         }
     };
 }
 
-#[derive(Clone)]
-struct ResultRow(postgres::Row);
+// Values of a search form, with the hidden values in place of the masks shown
+// in their fields
+fn with_hidden_values(mut values: Vec<String>, hidden_values: &[Option<String>]) -> Vec<String> {
+    for (value, hidden_value) in values.iter_mut().zip(hidden_values) {
+        if let Some(hidden_value) = hidden_value {
+            value.clone_from(hidden_value);
+        }
+    }
+    values
+}
 
-type IndexedRow = (usize, ResultRow);
+struct LogRoute {}
 
-impl cursive_table_view::TableViewItem<TableColumn> for IndexedRow {
-    fn to_column(&self, column: TableColumn) -> String {
-        match column {
-            TableColumn::Idx => self.0.to_string(),
-            TableColumn::DBCol(column) => {
-                let val: SQLValueAsString = self
-                    .1
-                    .0
-                    .try_get(column)
-                    .unwrap_or_else(|err| SQLValueAsString::new(err.to_string()));
-                val.take_string()
-            }
-        }
+impl Route for LogRoute {
+    fn mount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
+        let router = router.clone();
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(
+                    views::ScrollView::new(views::DebugView::new())
+                        .scroll_strategy(cursive::view::ScrollStrategy::StickToBottom)
+                        .full_screen(),
+                )
+                .on_event(cursive::event::Key::Esc, move |siv| router.pop(siv)),
+            )
+            .title("Logs"),
+        );
     }
 
-    fn cmp(&self, other: &Self, column: TableColumn) -> std::cmp::Ordering
-    where
-        Self: Sized,
-    {
-        match column {
-            TableColumn::Idx => self.0.cmp(&other.0),
-            TableColumn::DBCol(_) => {
-                let self_val = self.to_column(column);
-                let other_val = other.to_column(column);
-                self_val.cmp(&other_val)
-            }
-        }
+    fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
+        pop_route_layer(siv);
     }
 }
 
-fn col_size<'a>(rows: &'a [postgres::Row], col: usize) -> usize {
-    let name_size = rows
-        .first()
-        .map(|row| row.columns()[col].name().len())
-        .unwrap_or(0);
-    let max_col_size = rows
-        .iter()
-        .map(|row| {
-            row.try_get::<'a, usize, SQLValueAsString>(col)
-                .map(|v| v.take_string().len())
-                .unwrap_or(0)
-        })
-        .max()
-        .unwrap_or(0);
-
-    std::cmp::min(
-        32, // clip to 32 chars
-        std::cmp::max(name_size, max_col_size),
-    )
+#[derive(Default)]
+struct SqlConsoleState {
+    sql: String,
+    params: String,
+    resource_id: Option<String>,
 }
 
-struct QueryResultsRoute {
-    resource_id: String,
-    title: String,
-    rows: Vec<postgres::Row>,
+#[derive(Default)]
+struct SqlConsoleRoute {
+    // Kept across remounts, for example when coming back from the results
+    state: Arc<Mutex<SqlConsoleState>>,
 }
 
-impl Route for QueryResultsRoute {
+impl Route for SqlConsoleRoute {
     fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
         let router = router.clone();
-        siv.add_layer(views::Dialog::around(
-            views::OnEventView::new(build_query_results(
-                Arc::clone(&app_data_ptr),
-                &router,
-                &self.resource_id,
-                &self.title,
-                &self.rows,
+        let view = build_sql_console(Arc::clone(&app_data_ptr), &router, &self.state);
+        add_route_layer(
+            siv,
+            views::Dialog::around(views::OnEventView::new(view).on_event(
+                cursive::event::Key::Esc,
+                move |siv| {
+                    router.pop(siv);
+                },
             ))
-            .on_event(cursive::event::Key::Esc, move |siv| {
-                router.pop(siv);
-            }),
-        ));
+            .title("SQL console (read only)"),
+        );
     }
 
     fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
-        siv.pop_layer();
+        pop_route_layer(siv);
     }
 }
 
-#[derive(Clone, Copy, Hash, Eq, PartialEq)]
-enum TableColumn {
-    Idx,
-    DBCol(usize),
-}
-
-fn build_query_results(
+fn build_sql_console(
     app_data_ptr: AppDataPtr,
     router: &Router,
-    resource_id: &str,
-    title: &str,
-    rows: &[postgres::Row],
+    state: &Arc<Mutex<SqlConsoleState>>,
 ) -> impl cursive::view::View {
-    let mut table = cursive_table_view::TableView::<(usize, ResultRow), TableColumn>::new();
-
-    if !rows.is_empty() {
-        let first = &rows[0];
-
-        table.add_column(TableColumn::Idx, "#", |col| {
-            col.width((rows.len().ilog10() + 1) as usize)
-        });
+    let state_ref = state.lock().unwrap();
 
-        for (idx, col) in first.columns().iter().enumerate() {
-            table.add_column(TableColumn::DBCol(idx), col.name(), |col| {
-                col.width(col_size(rows, idx))
-            });
-        }
+    let mut resources: Vec<(String, String)> = {
+        let app_data = app_data_ptr.lock().unwrap();
+        app_data
+            .resources
+            .iter()
+            .map(|(id, r)| (r.name.clone(), id.clone()))
+            .collect()
+    };
+    resources.sort();
 
-        table.set_items(
-            rows.iter()
-                .enumerate()
-                .map(|(idx, r)| (idx, ResultRow(r.clone())))
-                .collect(),
-        );
-        table.set_on_submit(|siv: &mut cursive::Cursive, _row: usize, index: usize| {
-            let (_, row) = siv
-                .call_on_name(
-                    "results",
-                    |table: &mut cursive_table_view::TableView<IndexedRow, TableColumn>| {
-                        table.borrow_item(index).unwrap().clone()
-                    },
-                )
-                .expect("missing results view");
-            siv.add_layer(views::Dialog::around(build_row_view(&row)));
-        });
+    let mut resource_select = views::SelectView::new().popup();
+    resource_select.add_all(resources);
+    if let Some(idx) = state_ref
+        .resource_id
+        .as_ref()
+        .and_then(|id| resource_select.iter().position(|(_, r)| r == id))
+    {
+        resource_select.set_selection(idx);
     }
 
-    let table_with_events = {
-        let resource_id = resource_id.to_owned();
+    let run_button = {
+        let app_data_ptr = Arc::clone(&app_data_ptr);
         let router = router.clone();
-        views::OnEventView::new(table.with_name("results")).on_event('l', move |siv| {
-            if let Some((_, row)) = siv
-                .call_on_name(
-                    "results",
-                    |table: &mut cursive_table_view::TableView<IndexedRow, TableColumn>| {
-                        table
-                            .item()
-                            .map(|idx| table.borrow_item(idx).unwrap().clone())
-                    },
-                )
-                .expect("missing results view")
-            {
-                on_show_links(Arc::clone(&app_data_ptr), siv, &router, &resource_id, &row);
-            }
+        let state = Arc::clone(state);
+        views::Button::new("Run", move |siv| {
+            on_run_sql_console(Arc::clone(&app_data_ptr), siv, &router, &state)
         })
     };
 
-    views::LinearLayout::vertical()
-        .child(views::TextView::new(format!("Query results: {title}")))
-        .child(table_with_events.full_screen())
-}
+    let save_button = {
+        let state = Arc::clone(state);
+        views::Button::new("Save as search", move |siv| {
+            on_save_sql_console(Arc::clone(&app_data_ptr), siv, &state)
+        })
+    };
 
-fn build_query_error(err: &anyhow::Error) -> impl cursive::view::View {
     views::LinearLayout::vertical()
-        .child(views::TextView::new("Query Error"))
-        .child(views::TextView::new(err.to_string()))
-        .child(views::Button::new("OK", |s| {
-            s.pop_layer();
-        }))
+        .child(
+            views::Panel::new(
+                views::TextArea::new()
+                    .content(&state_ref.sql)
+                    .with_name("console_sql")
+                    .min_height(8)
+                    .min_width(72),
+            )
+            .title("Query"),
+        )
+        .child(
+            views::Panel::new(
+                views::TextArea::new()
+                    .content(&state_ref.params)
+                    .with_name("console_params")
+                    .min_height(2),
+            )
+            .title("Parameters ($1, $2...), one per line"),
+        )
+        .child(
+            views::LinearLayout::horizontal()
+                .child(views::TextView::new("Rows are: "))
+                .child(resource_select.with_name("console_resource")),
+        )
+        .child(
+            views::LinearLayout::horizontal()
+                .child(run_button)
+                .child(views::DummyView)
+                .child(save_button),
+        )
 }
 
-fn build_row_view<'a>(row: &'a ResultRow) -> impl cursive::view::View {
-    let row = &row.0;
-    let mut values = views::LinearLayout::vertical();
+fn gather_sql_console_inputs(
+    siv: &mut cursive::Cursive,
+    state: &Arc<Mutex<SqlConsoleState>>,
+) -> Option<(String, Vec<String>, String)> {
+    let sql = siv
+        .call_on_name("console_sql", |v: &mut views::TextArea| {
+            v.get_content().to_owned()
+        })
+        .expect("missing console_sql view");
+    let params = siv
+        .call_on_name("console_params", |v: &mut views::TextArea| {
+            v.get_content().to_owned()
+        })
+        .expect("missing console_params view");
+    let resource_id = siv
+        .call_on_name("console_resource", |v: &mut views::SelectView<String>| {
+            v.selection()
+        })
+        .expect("missing console_resource view")
+        .map(|id| (*id).clone());
 
-    for (idx, col) in row.columns().iter().enumerate() {
-        let view = match row.try_get::<'a, usize, SQLValueAsString>(idx) {
-            Ok(v) => cursive::views::TextView::new(v.as_str()),
-            Err(err) => cursive::views::TextView::new(err.to_string()),
-        };
-        values.add_child(views::Panel::new(view).title(col.name()));
-    }
+    let mut state = state.lock().unwrap();
+    state.sql = sql.clone();
+    state.params = params.clone();
+    state.resource_id = resource_id.clone();
 
-    views::LinearLayout::vertical()
-        .child(values)
-        .child(views::Button::new("Close", |s| {
-            s.pop_layer();
-        }))
+    let params = params.lines().map(str::to_owned).collect();
+    Some((sql, params, resource_id?))
 }
 
-fn on_show_links(
+fn sql_console_param_types(
+    app_data_ptr: &AppDataPtr,
+    connection: &str,
+    sql: &str,
+) -> Result<Vec<SearchParamType>> {
+    query_param_types(app_data_ptr, connection, sql)?
+        .iter()
+        .enumerate()
+        .map(|(idx, ty)| {
+            search_param_type(ty)
+                .with_context(|| format!("unsupported type {ty} for parameter ${}", idx + 1))
+        })
+        .collect()
+}
+
+fn on_run_sql_console(
     app_data_ptr: AppDataPtr,
     siv: &mut cursive::Cursive,
     router: &Router,
-    resource_id: &str,
-    row: &ResultRow,
+    state: &Arc<Mutex<SqlConsoleState>>,
 ) {
-    siv.add_layer(views::Dialog::around(
-        views::OnEventView::new(build_link_picker(
-            Arc::clone(&app_data_ptr),
+    let Some((sql, params, resource_id)) = gather_sql_console_inputs(siv, state) else {
+        return;
+    };
+    let r = get_resource(&app_data_ptr, &resource_id);
+
+    let results =
+        sql_console_param_types(&app_data_ptr, r.connection_name(), &sql).and_then(|param_types| {
+            if param_types.len() != params.len() {
+                bail!(
+                    "the query has {} parameters but {} values were given",
+                    param_types.len(),
+                    params.len()
+                );
+            }
+
+            let param_values = param_types
+                .into_iter()
+                .zip(params.iter())
+                .enumerate()
+                .map(|(idx, (ty, value))| {
+                    sql_value_from_string(value, ty)
+                        .with_context(|| format!("error parsing parameter ${}", idx + 1))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            let (rows, info) = run_query(&app_data_ptr, r.connection_name(), &sql, param_values)?;
+
+            let mut entry = AuditEntry::new(&resource_id);
+            entry.sql = Some(sql.clone());
+            for (idx, value) in params.iter().enumerate() {
+                entry.add_param(&format!("${}", idx + 1), None, std::slice::from_ref(value));
+            }
+            entry.rows = rows.len();
+            record_audit(&app_data_ptr, entry)?;
+
+            Ok((rows, info))
+        });
+
+    match results {
+        Ok((rows, info)) => router.push(
+            siv,
+            Box::new(QueryResultsRoute {
+                resource_id,
+                title: format!("{} / SQL console", &r.name),
+                rows,
+                row_sources: Vec::new(),
+                queries: vec![info],
+                selection: Default::default(),
+                show_hidden: Default::default(),
+            }),
+        ),
+        Err(err) => {
+            log::error!("Error running query: {err:#}");
+            siv.add_layer(views::Dialog::around(build_query_error(&err)));
+        }
+    }
+}
+
+fn on_save_sql_console(
+    app_data_ptr: AppDataPtr,
+    siv: &mut cursive::Cursive,
+    state: &Arc<Mutex<SqlConsoleState>>,
+) {
+    let Some((sql, _, resource_id)) = gather_sql_console_inputs(siv, state) else {
+        return;
+    };
+    let r = get_resource(&app_data_ptr, &resource_id);
+
+    match sql_console_param_types(&app_data_ptr, r.connection_name(), &sql) {
+        Ok(param_types) => siv.add_layer(views::Dialog::around(build_save_search(
+            app_data_ptr,
+            resource_id,
+            sql,
+            param_types,
+        ))),
+        Err(err) => {
+            log::error!("Error preparing query: {err:#}");
+            siv.add_layer(views::Dialog::around(build_query_error(&err)));
+        }
+    }
+}
+
+fn build_save_search(
+    app_data_ptr: AppDataPtr,
+    resource_id: String,
+    sql: String,
+    param_types: Vec<SearchParamType>,
+) -> impl cursive::view::View {
+    let r = get_resource(&app_data_ptr, &resource_id);
+    let mut layout = views::LinearLayout::vertical()
+        .child(views::TextView::new(format!(
+            "Save as a search of {}",
+            &r.name
+        )))
+        .child(
+            views::Panel::new(
+                views::EditView::new()
+                    .with_name("save_search_id")
+                    .min_width(32),
+            )
+            .title("Search name"),
+        );
+
+    for (idx, ty) in param_types.iter().enumerate() {
+        layout.add_child(
+            views::Panel::new(
+                views::EditView::new()
+                    .content(format!("Param {}", idx + 1))
+                    .with_name(format!("save_search_param_{idx}")),
+            )
+            .title(format!("Name of ${} ({ty})", idx + 1)),
+        );
+    }
+
+    layout
+        .child(views::Button::new("Save", move |siv| {
+            let search_id = siv
+                .call_on_name("save_search_id", |v: &mut views::EditView| {
+                    v.get_content().to_string()
+                })
+                .expect("missing save_search_id view");
+            let params = param_types
+                .iter()
+                .enumerate()
+                .map(|(idx, ty)| SearchParam {
+                    name: siv
+                        .call_on_name(
+                            &format!("save_search_param_{idx}"),
+                            |v: &mut views::EditView| v.get_content().to_string(),
+                        )
+                        .expect("missing search param view"),
+                    description: None,
+                    ty: Some(ty.clone()),
+                    sensitive: false,
+                })
+                .collect();
+            let search = Search {
+                query: sql.clone(),
+                description: None,
+                params,
+                order: None,
+                shortcut: None,
+                position: 0,
+            };
+
+            match save_search(&app_data_ptr, &resource_id, &search_id, &search) {
+                Ok(path) => {
+                    siv.pop_layer();
+                    set_status(
+                        siv,
+                        format!("Search {search_id} saved to {}", path.display()),
+                    );
+                }
+                Err(err) => {
+                    log::error!("Error saving search: {err:#}");
+                    siv.add_layer(views::Dialog::around(build_query_error(&err)));
+                }
+            }
+        }))
+        .child(views::Button::new("Cancel", |siv| {
+            siv.pop_layer();
+        }))
+}
+
+// Adds a search to the configuration file defining its resource, the
+// configuration is then reloaded like after any other change
+fn save_search(
+    app_data_ptr: &AppDataPtr,
+    resource_id: &str,
+    search_id: &str,
+    search: &Search,
+) -> Result<PathBuf> {
+    if search_id.is_empty() {
+        bail!("the search name can't be empty");
+    }
+
+    let app_data = app_data_ptr.lock().unwrap();
+    let resource = app_data
+        .resources
+        .get(resource_id)
+        .with_context(|| format!("unknown resource {resource_id}"))?;
+    if resource.search.contains_key(search_id) {
+        bail!("{} already has a search named {search_id}", &resource.name);
+    }
+
+    let location = app_data
+        .locations
+        .get(resource_id)
+        .with_context(|| format!("unknown location for resource {resource_id}"))?;
+    append_search(&location.file, resource_id, search_id, search)?;
+
+    Ok(location.file.clone())
+}
+
+#[derive(Clone)]
+struct ResultRow(postgres::Row);
+
+struct ValueFormatter {
+    resource_name: String,
+    title: Option<String>,
+    display: ResourceDisplay,
+    // Empty when running with --unmask
+    redact: HashMap<String, Redaction>,
+    default_timezone: Option<TimeZone>,
+}
+
+impl ValueFormatter {
+    fn new(app_data_ptr: &AppDataPtr, resource_id: &str) -> Self {
+        let r = get_resource(app_data_ptr, resource_id);
+        let app_data = app_data_ptr.lock().unwrap();
+        ValueFormatter {
+            resource_name: r.name,
+            title: r.title,
+            display: r.display,
+            redact: if app_data.settings.unmask {
+                HashMap::new()
+            } else {
+                r.redact
+            },
+            default_timezone: app_data.settings.default_timezone.clone(),
+        }
+    }
+
+    fn format(&self, row: &postgres::Row, idx: usize) -> String {
+        let name = row.columns()[idx].name();
+        if let Some(redaction) = self.redact.get(name)
+            && !is_null(row, idx)
+        {
+            return match row.try_get::<_, SQLValueAsString>(idx) {
+                Ok(value) => redact(value.as_str(), redaction),
+                Err(_) => MASK.to_owned(),
+            };
+        }
+
+        let format = self
+            .display
+            .columns
+            .get(name)
+            .and_then(|c| c.format.as_ref());
+        format_value(row, idx, format, self.default_timezone.as_ref())
+    }
+
+    // Redacted columns are sorted as shown, so that their order doesn't tell
+    // their values
+    fn sort_key(&self, row: &postgres::Row, idx: usize) -> SortKey {
+        if self.is_redacted(row.columns()[idx].name()) && !is_null(row, idx) {
+            SortKey::Text(self.format(row, idx))
+        } else {
+            SortKey::new(row, idx)
+        }
+    }
+
+    fn is_redacted(&self, column: &str) -> bool {
+        self.redact.contains_key(column)
+    }
+
+    fn title(&self, row: &ResultRow) -> Option<String> {
+        self.render_title(|col| column_value_as_string(row, col))
+    }
+
+    fn render_title(&self, column_value: impl Fn(&str) -> Result<String>) -> Option<String> {
+        let template = self.title.as_ref()?;
+        render_template(template, |col| {
+            let value = column_value(col)?;
+            Ok(match self.redact.get(col) {
+                Some(redaction) => redact(&value, redaction),
+                None => value,
+            })
+        })
+        .inspect_err(|err| log::warn!("Error rendering title of {}: {err}", &self.resource_name))
+        .ok()
+    }
+
+    fn hides_param(&self, param: &ColumnExpression) -> bool {
+        match param {
+            ColumnExpression::Name(name) => self.is_redacted(name),
+            ColumnExpression::JsonPath {
+                col_and_path: (col_name, _),
+            } => self.is_redacted(col_name),
+            ColumnExpression::Template { template } => {
+                parse_template(template).is_ok_and(|parts| {
+                    parts.iter().any(
+                        |part| matches!(part, TemplatePart::Column(col) if self.is_redacted(col)),
+                    )
+                })
+            }
+            ColumnExpression::Value { .. } | ColumnExpression::Prompt { .. } => false,
+        }
+    }
+
+    fn param_value(&self, param: &ColumnExpression, value: String) -> String {
+        match param {
+            ColumnExpression::Name(name) => match self.redact.get(name) {
+                Some(redaction) => redact(&value, redaction),
+                None => value,
+            },
+            _ if self.hides_param(param) => MASK.to_owned(),
+            _ => value,
+        }
+    }
+
+    fn row_style(&self, row: &ResultRow) -> RowStyle {
+        let columns: Vec<&str> = row.0.columns().iter().map(|c| c.name()).collect();
+        style_row(
+            &self.display.styles,
+            &columns,
+            |condition| evaluate_link_condition(condition.clone(), row),
+            |idx| is_null(&row.0, idx),
+        )
+    }
+}
+
+fn style_row(
+    rules: &[StyleRule],
+    columns: &[&str],
+    condition_matches: impl Fn(&Option<LinkCondition>) -> Result<bool>,
+    is_null: impl Fn(usize) -> bool,
+) -> RowStyle {
+    let mut res = RowStyle::default();
+
+    for rule in rules {
+        let Ok(style) = rule.style.parse::<Style>() else {
+            continue; // styles are validated when loading the resources
+        };
+
+        let matches = condition_matches(&rule.condition).unwrap_or_else(|err| {
+            log::warn!("Error evaluating style condition: {err}");
+            false
+        });
+        if !matches {
+            continue;
+        }
+
+        let targets: Vec<usize> = match &rule.column {
+            Some(name) => columns.iter().position(|c| c == name).into_iter().collect(),
+            None if rule.null => (0..columns.len()).collect(),
+            None => {
+                res.row = Some(res.row.unwrap_or_else(Style::none).combine(style));
+                continue;
+            }
+        };
+
+        for idx in targets {
+            if rule.null && !is_null(idx) {
+                continue;
+            }
+            let cell = res.cells.entry(idx).or_insert_with(Style::none);
+            *cell = cell.combine(style);
+        }
+    }
+
+    res
+}
+
+fn is_null(row: &postgres::Row, idx: usize) -> bool {
+    matches!(row.try_get::<_, Option<SQLValueAsString>>(idx), Ok(None))
+}
+
+#[derive(Clone, Default)]
+struct RowStyle {
+    row: Option<Style>,
+    cells: HashMap<usize, Style>,
+}
+
+impl RowStyle {
+    fn column(&self, idx: usize) -> Option<Style> {
+        match (self.row, self.cells.get(&idx)) {
+            (Some(row), Some(cell)) => Some(row.combine(*cell)),
+            (row, cell) => row.or(cell.copied()),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct IndexedRow {
+    idx: usize,
+    row: ResultRow,
+    source: Option<String>,
+    selected: bool,
+    formatter: Arc<ValueFormatter>,
+    style: RowStyle,
+    // By column index
+    sort_keys: Vec<SortKey>,
+}
+
+impl cursive_table_view::TableViewItem<TableColumn> for IndexedRow {
+    fn to_column(&self, column: TableColumn) -> String {
+        match column {
+            TableColumn::Idx => {
+                format!("{}{}", if self.selected { "*" } else { " " }, self.idx)
+            }
+            TableColumn::Source => self.source.clone().unwrap_or_default(),
+            TableColumn::DBCol(column) => self.formatter.format(&self.row.0, column),
+        }
+    }
+
+    fn cmp(&self, other: &Self, column: TableColumn) -> std::cmp::Ordering
+    where
+        Self: Sized,
+    {
+        match column {
+            TableColumn::Idx => self.idx.cmp(&other.idx),
+            TableColumn::Source => self.source.cmp(&other.source),
+            TableColumn::DBCol(idx) => self.sort_keys[idx].cmp(&other.sort_keys[idx]),
+        }
+        // Never consider two rows equal, StyledTable relies on it
+        .then(self.idx.cmp(&other.idx))
+    }
+}
+
+impl StyledTableItem<TableColumn> for IndexedRow {
+    fn column_style(&self, column: TableColumn) -> Option<Style> {
+        match column {
+            TableColumn::Idx | TableColumn::Source => self.style.row,
+            TableColumn::DBCol(idx) => self.style.column(idx),
+        }
+    }
+}
+
+fn col_size(
+    rows: &[postgres::Row],
+    col: usize,
+    label: &str,
+    max_width: usize,
+    formatter: &ValueFormatter,
+) -> usize {
+    let name_size = label.len();
+    let max_col_size = rows
+        .iter()
+        .map(|row| formatter.format(row, col).chars().count())
+        .max()
+        .unwrap_or(0);
+
+    std::cmp::min(max_width, std::cmp::max(name_size, max_col_size))
+}
+
+const DEFAULT_MAX_COL_WIDTH: usize = 32;
+
+fn displayed_columns<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    display: &ResourceDisplay,
+    show_hidden: bool,
+) -> Vec<usize> {
+    let names: Vec<&str> = names.into_iter().collect();
+    let mut res: Vec<usize> = display
+        .order
+        .iter()
+        .filter_map(|name| names.iter().position(|n| n == name))
+        .collect();
+
+    for idx in 0..names.len() {
+        if !res.contains(&idx) {
+            res.push(idx);
+        }
+    }
+
+    res.retain(|idx| show_hidden || !display.hidden.iter().any(|h| h == names[*idx]));
+    res
+}
+
+struct QueryResultsRoute {
+    resource_id: String,
+    title: String,
+    rows: Vec<postgres::Row>,
+    // When following a link for several rows, the source row each result came from
+    row_sources: Vec<String>,
+    // The queries run to get the rows, several when following a link for
+    // several rows without a batch search
+    queries: Vec<QueryInfo>,
+    selection: Arc<Mutex<HashSet<usize>>>,
+    show_hidden: Arc<AtomicBool>,
+}
+
+impl Route for QueryResultsRoute {
+    fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
+        let router = router.clone();
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(build_query_results(
+                    Arc::clone(&app_data_ptr),
+                    &router,
+                    self,
+                ))
+                .on_event(cursive::event::Key::Esc, move |siv| {
+                    router.pop(siv);
+                }),
+            ),
+        );
+    }
+
+    fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
+        pop_route_layer(siv);
+    }
+
+    fn is_valid(&self, resources: &HashMap<String, Resource>) -> bool {
+        resources.contains_key(&self.resource_id)
+    }
+
+    fn actions(
+        &self,
+        app_data_ptr: &AppDataPtr,
+        siv: &mut cursive::Cursive,
+        router: &Router,
+    ) -> Vec<Action> {
+        let mut actions = vec![
+            Action::key(
+                "Open the highlighted row",
+                "Enter",
+                cursive::event::Key::Enter,
+            ),
+            Action::key("Show the links of the rows", "l", 'l'),
+            Action::key("Select or unselect the highlighted row", "Space", ' '),
+            Action::key("Select or unselect all the rows", "*", '*'),
+            Action::key("Show the query info", "i", 'i'),
+        ];
+        let r = get_resource(app_data_ptr, &self.resource_id);
+        if !r.display.hidden.is_empty() {
+            actions.push(Action::key("Show or hide the hidden columns", "h", 'h'));
+        }
+        actions.extend(link_actions(
+            app_data_ptr,
+            router,
+            &self.resource_id,
+            results_rows(siv),
+        ));
+        actions
+    }
+}
+
+// Rows whose links are followed: the selected ones, else the highlighted one
+fn results_rows(siv: &mut cursive::Cursive) -> Vec<IndexedRow> {
+    siv.call_on_name(
+        "results",
+        |table: &mut cursive_table_view::TableView<IndexedRow, TableColumn>| {
+            let selected: Vec<IndexedRow> = table
+                .borrow_items()
+                .iter()
+                .filter(|r| r.selected)
+                .cloned()
+                .collect();
+            if selected.is_empty() {
+                table
+                    .item()
+                    .map(|idx| table.borrow_item(idx).unwrap().clone())
+                    .into_iter()
+                    .collect()
+            } else {
+                selected
+            }
+        },
+    )
+    .expect("missing results view")
+}
+
+#[derive(Clone, Copy, Hash, Eq, PartialEq)]
+enum TableColumn {
+    Idx,
+    Source,
+    DBCol(usize),
+}
+
+fn build_query_results(
+    app_data_ptr: AppDataPtr,
+    router: &Router,
+    results: &QueryResultsRoute,
+) -> impl cursive::view::View {
+    let resource_id = results.resource_id.as_str();
+    let title = results.title.as_str();
+    let rows = results.rows.as_slice();
+    let row_sources = results.row_sources.as_slice();
+    let selection = Arc::clone(&results.selection);
+    let show_hidden = Arc::clone(&results.show_hidden);
+    let mut table = cursive_table_view::TableView::<IndexedRow, TableColumn>::new();
+    let formatter = Arc::new(ValueFormatter::new(&app_data_ptr, resource_id));
+    let display = &formatter.display;
+    let mut column_layouts = Vec::new();
+
+    if !rows.is_empty() {
+        let first = &rows[0];
+
+        let idx_width = (rows.len().ilog10() + 2) as usize;
+        table.add_column(TableColumn::Idx, "#", |col| col.width(idx_width));
+        column_layouts.push(ColumnLayout {
+            column: TableColumn::Idx,
+            width: idx_width,
+            align: cursive::align::HAlign::Left,
+        });
+
+        if !row_sources.is_empty() {
+            let width = row_sources.iter().map(|s| s.len()).max().unwrap_or(0);
+            let width = std::cmp::min(32, std::cmp::max("Source".len(), width));
+            table.add_column(TableColumn::Source, "Source", |col| col.width(width));
+            column_layouts.push(ColumnLayout {
+                column: TableColumn::Source,
+                width,
+                align: cursive::align::HAlign::Left,
+            });
+        }
+
+        let columns = first.columns();
+        for idx in displayed_columns(
+            columns.iter().map(|c| c.name()),
+            display,
+            show_hidden.load(Ordering::Relaxed),
+        ) {
+            let name = columns[idx].name();
+            let label = display.label(name);
+            let col_display = display.columns.get(name).cloned().unwrap_or_default();
+            let width = col_display.width.unwrap_or_else(|| {
+                col_size(
+                    rows,
+                    idx,
+                    label,
+                    col_display.max_width.unwrap_or(DEFAULT_MAX_COL_WIDTH),
+                    &formatter,
+                )
+            });
+
+            let align = match col_display.align {
+                Some(ColumnAlign::Left) | None => cursive::align::HAlign::Left,
+                Some(ColumnAlign::Center) => cursive::align::HAlign::Center,
+                Some(ColumnAlign::Right) => cursive::align::HAlign::Right,
+            };
+
+            table.add_column(TableColumn::DBCol(idx), label, |col| {
+                col.width(width).align(align)
+            });
+            column_layouts.push(ColumnLayout {
+                column: TableColumn::DBCol(idx),
+                width,
+                align,
+            });
+        }
+
+        let selected = selection.lock().unwrap();
+        table.set_items(
+            rows.iter()
+                .enumerate()
+                .map(|(idx, r)| {
+                    let row = ResultRow(r.clone());
+                    IndexedRow {
+                        idx,
+                        style: formatter.row_style(&row),
+                        sort_keys: (0..r.len()).map(|col| formatter.sort_key(r, col)).collect(),
+                        row,
+                        source: row_sources.get(idx).cloned(),
+                        selected: selected.contains(&idx),
+                        formatter: Arc::clone(&formatter),
+                    }
+                })
+                .collect(),
+        );
+        let resource_id = resource_id.to_owned();
+        let router = router.clone();
+        table.set_on_submit(
+            move |siv: &mut cursive::Cursive, _row: usize, index: usize| {
+                let row = siv
+                    .call_on_name(
+                        "results",
+                        |table: &mut cursive_table_view::TableView<IndexedRow, TableColumn>| {
+                            table.borrow_item(index).unwrap().clone()
+                        },
+                    )
+                    .expect("missing results view");
+                router.push(
+                    siv,
+                    Box::new(RowRoute {
+                        resource_id: resource_id.clone(),
+                        row,
+                        relation_counts: Default::default(),
+                        stop_counting: Default::default(),
+                    }),
+                );
+            },
+        );
+    }
+
+    let table_with_events = {
+        let resource_id = resource_id.to_owned();
+        let links_router = router.clone();
+        let toggle_selection = Arc::clone(&selection);
+        let toggle_all_selection = Arc::clone(&selection);
+        views::OnEventView::new(StyledTable::new(table.with_name("results"), column_layouts))
+            .on_event('l', move |siv| {
+                let rows = results_rows(siv);
+                if !rows.is_empty() {
+                    on_show_links(
+                        Arc::clone(&app_data_ptr),
+                        siv,
+                        &links_router,
+                        &resource_id,
+                        rows,
+                    );
+                }
+            })
+            .on_event(' ', move |siv| {
+                siv.call_on_name(
+                    "results",
+                    |table: &mut cursive_table_view::TableView<IndexedRow, TableColumn>| {
+                        let Some(idx) = table.item() else {
+                            return;
+                        };
+                        let item = table.borrow_item_mut(idx).unwrap();
+                        item.selected = !item.selected;
+
+                        let mut selection = toggle_selection.lock().unwrap();
+                        if item.selected {
+                            selection.insert(item.idx);
+                        } else {
+                            selection.remove(&item.idx);
+                        }
+                    },
+                );
+            })
+            .on_event('h', {
+                let router = router.clone();
+                move |siv| {
+                    show_hidden.fetch_xor(true, Ordering::Relaxed);
+                    router.remount(siv);
+                }
+            })
+            .on_event('i', {
+                let router = router.clone();
+                let queries = results.queries.clone();
+                move |siv| {
+                    router.push(
+                        siv,
+                        Box::new(QueryInfoRoute {
+                            queries: queries.clone(),
+                        }),
+                    );
+                }
+            })
+            .on_event('*', move |siv| {
+                siv.call_on_name(
+                    "results",
+                    |table: &mut cursive_table_view::TableView<IndexedRow, TableColumn>| {
+                        let items = table.borrow_items_mut();
+                        let select = !items.iter().all(|r| r.selected);
+
+                        let mut selection = toggle_all_selection.lock().unwrap();
+                        selection.clear();
+                        for item in items {
+                            item.selected = select;
+                            if select {
+                                selection.insert(item.idx);
+                            }
+                        }
+                    },
+                );
+            })
+    };
+
+    let title = if display.hidden.is_empty() {
+        format!("Query results: {title} (i: query info)")
+    } else {
+        format!("Query results: {title} (i: query info, h: toggle hidden columns)")
+    };
+
+    views::LinearLayout::vertical()
+        .child(views::TextView::new(title))
+        .child(table_with_events.full_screen())
+}
+
+struct QueryInfoRoute {
+    queries: Vec<QueryInfo>,
+}
+
+impl Route for QueryInfoRoute {
+    fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
+        let mut layout = views::LinearLayout::vertical();
+
+        for (idx, info) in self.queries.iter().enumerate() {
+            let panel = views::Panel::new(
+                views::LinearLayout::vertical()
+                    .child(views::TextView::new(query_info_text(info)))
+                    .child(views::Button::new("Explain", {
+                        let app_data_ptr = Arc::clone(&app_data_ptr);
+                        let router = router.clone();
+                        let info = info.clone();
+                        move |siv| on_explain(Arc::clone(&app_data_ptr), siv, &router, &info)
+                    })),
+            );
+            layout.add_child(if self.queries.len() > 1 {
+                panel.title(format!("Query {}/{}", idx + 1, self.queries.len()))
+            } else {
+                panel
+            });
+        }
+
+        let router = router.clone();
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(views::ScrollView::new(layout))
+                    .on_event(cursive::event::Key::Esc, move |siv| router.pop(siv)),
+            )
+            .title("Query info"),
+        );
+    }
+
+    fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
+        pop_route_layer(siv);
+    }
+}
+
+fn query_info_text(info: &QueryInfo) -> String {
+    let duration = if info.duration < Duration::from_secs(1) {
+        format!("{:.1} ms", info.duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2} s", info.duration.as_secs_f64())
+    };
+
+    let mut text = format!(
+        "Connection: {}\nDuration: {duration}\nRows: {}\n\n{}\n",
+        &info.connection,
+        info.row_count,
+        info.sql.trim()
+    );
+
+    if !info.params.is_empty() {
+        text.push_str("\nParameters:\n");
+        for (idx, (ty, value)) in info.param_types.iter().zip(info.params.iter()).enumerate() {
+            if info.hidden_params.get(idx) == Some(&true) {
+                let _ = writeln!(&mut text, "  ${} ({ty}) = {MASK}", idx + 1);
+            } else {
+                let _ = writeln!(&mut text, "  ${} ({ty}) = {value:?}", idx + 1);
+            }
+        }
+    }
+
+    text
+}
+
+fn on_explain(
+    app_data_ptr: AppDataPtr,
+    siv: &mut cursive::Cursive,
+    router: &Router,
+    info: &QueryInfo,
+) {
+    // Plans can show the parameters as literals. Nothing is hidden with --unmask
+    if info.hidden_params.contains(&true) {
+        siv.add_layer(views::Dialog::info(
+            "Explain is disabled for queries with parameters bound from redacted columns, unless running with --unmask",
+        ));
+        return;
+    }
+
+    let plan = execute_query(
+        &app_data_ptr,
+        &info.connection,
+        &format!("EXPLAIN (ANALYZE off, FORMAT JSON) {}", &info.sql),
+        &info.params,
+    )
+    .and_then(|(rows, _, _)| {
+        rows.first()
+            .context("EXPLAIN returned no rows")?
+            .try_get::<_, serde_json::Value>(0)
+            .context("error parsing query plan")
+    });
+
+    match plan {
+        Ok(plan) => router.push(siv, Box::new(ExplainRoute { plan })),
+        Err(err) => {
+            log::error!("Error explaining query: {err:#}");
+            siv.add_layer(views::Dialog::around(build_query_error(&err)));
+        }
+    }
+}
+
+struct ExplainRoute {
+    plan: serde_json::Value,
+}
+
+impl Route for ExplainRoute {
+    fn mount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
+        let buttons = views::LinearLayout::horizontal()
+            .child(views::Button::new("JSON viewer", {
+                let router = router.clone();
+                let plan = self.plan.clone();
+                move |siv| {
+                    router.push(
+                        siv,
+                        Box::new(JsonRoute {
+                            column: "plan".to_owned(),
+                            value: plan.clone(),
+                            collapsed: Default::default(),
+                        }),
+                    )
+                }
+            }))
+            .child(views::DummyView)
+            .child(views::Button::new("Close", {
+                let router = router.clone();
+                move |siv| router.pop(siv)
+            }));
+
+        let router = router.clone();
+        add_route_layer(
+            siv,
+            views::Dialog::around(
+                views::OnEventView::new(
+                    views::LinearLayout::vertical()
+                        .child(views::ScrollView::new(views::TextView::new(
+                            plan_lines(&self.plan).join("\n"),
+                        )))
+                        .child(buttons),
+                )
+                .on_event(cursive::event::Key::Esc, move |siv| router.pop(siv)),
+            )
+            .title("Query plan"),
+        );
+    }
+
+    fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
+        pop_route_layer(siv);
+    }
+}
+
+fn build_query_error(err: &anyhow::Error) -> impl cursive::view::View {
+    views::LinearLayout::vertical()
+        .child(views::TextView::new("Query Error"))
+        .child(views::TextView::new(err.to_string()))
+        .child(views::Button::new("OK", |s| {
+            s.pop_layer();
+        }))
+}
+
+fn build_row_view<'a>(
+    row: &'a ResultRow,
+    formatter: &ValueFormatter,
+    router: &Router,
+) -> impl cursive::view::View {
+    let display = &formatter.display;
+    let style = formatter.row_style(row);
+    let row = &row.0;
+    let mut values = views::LinearLayout::vertical();
+
+    for (idx, col) in row.columns().iter().enumerate() {
+        let is_json = col.type_() == &postgres::types::Type::JSON
+            || col.type_() == &postgres::types::Type::JSONB;
+        let redacted = formatter.is_redacted(col.name());
+
+        if is_json
+            && !redacted
+            && let Ok(Some(value)) = row.try_get::<'a, usize, Option<serde_json::Value>>(idx)
+        {
+            let text = serde_json::to_string_pretty(&value).unwrap_or_else(|err| err.to_string());
+            let router = router.clone();
+            let column = col.name().to_owned();
+            let view = views::LinearLayout::vertical()
+                .child(views::TextView::new(text))
+                .child(views::Button::new("JSON viewer", move |siv| {
+                    router.push(
+                        siv,
+                        Box::new(JsonRoute {
+                            column: column.clone(),
+                            value: value.clone(),
+                            collapsed: Default::default(),
+                        }),
+                    )
+                }));
+            values.add_child(views::Panel::new(view).title(display.label(col.name())));
+            continue;
+        }
+
+        let text = match row.try_get::<'a, usize, SQLValueAsString>(idx) {
+            Ok(v) => {
+                let formatted = formatter.format(row, idx);
+                if redacted || formatted == v.as_str() {
+                    formatted
+                } else {
+                    format!("{formatted} ({})", v.as_str())
+                }
+            }
+            Err(err) => err.to_string(),
+        };
+        let view = match style.column(idx) {
+            Some(style) => views::TextView::new(StyledString::styled(text, style)),
+            None => views::TextView::new(text),
+        };
+        values.add_child(views::Panel::new(view).title(display.label(col.name())));
+    }
+
+    views::ScrollView::new(values)
+}
+
+struct JsonRoute {
+    column: String,
+    value: serde_json::Value,
+    collapsed: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Route for JsonRoute {
+    fn mount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
+        let mut tree = views::SelectView::<JsonTreeItem>::new();
+        fill_json_tree(
+            &mut tree,
+            &self.value,
+            &self.collapsed.lock().unwrap(),
+            None,
+        );
+
+        {
+            let value = self.value.clone();
+            let collapsed = Arc::clone(&self.collapsed);
+            tree.set_on_submit(move |siv, item: &JsonTreeItem| {
+                if !item.is_container {
+                    return;
+                }
+
+                let mut collapsed = collapsed.lock().unwrap();
+                if !collapsed.remove(&item.path) {
+                    collapsed.insert(item.path.clone());
+                }
+                siv.call_on_name("json_tree", |tree: &mut views::SelectView<JsonTreeItem>| {
+                    fill_json_tree(tree, &value, &collapsed, Some(&item.path));
+                });
+            });
+        }
+
+        let view = {
+            let router = router.clone();
+            let value = self.value.clone();
+            let column = self.column.clone();
+            views::OnEventView::new(
+                views::LinearLayout::vertical()
+                    .child(views::TextView::new(format!("JSON: {}", &self.column)))
+                    .child(views::ScrollView::new(tree.with_name("json_tree")).full_screen())
+                    .child(views::TextView::new(
+                        "Enter: collapse/expand, /: evaluate JSONPath, c: copy json_path",
+                    )),
+            )
+            .on_event('/', move |siv| {
+                siv.add_layer(views::Dialog::around(build_json_path_prompt(value.clone())))
+            })
+            .on_event('c', move |siv| {
+                let Some(item) = siv
+                    .call_on_name("json_tree", |tree: &mut views::SelectView<JsonTreeItem>| {
+                        tree.selection()
+                    })
+                    .flatten()
+                else {
+                    return;
+                };
+                on_copy_json_path(siv, &column, &item.path);
+            })
+            .on_event(cursive::event::Key::Esc, move |siv| {
+                router.pop(siv);
+            })
+        };
+
+        add_route_layer(siv, views::Dialog::around(view));
+    }
+
+    fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
+        pop_route_layer(siv);
+    }
+
+    fn actions(
+        &self,
+        _app_data_ptr: &AppDataPtr,
+        _siv: &mut cursive::Cursive,
+        _router: &Router,
+    ) -> Vec<Action> {
+        vec![
+            Action::key(
+                "Collapse or expand the highlighted value",
+                "Enter",
+                cursive::event::Key::Enter,
+            ),
+            Action::key("Evaluate a JSONPath expression", "/", '/'),
+            Action::key("Copy the json_path of the highlighted value", "c", 'c'),
+        ]
+    }
+}
+
+#[derive(Clone)]
+struct JsonTreeItem {
+    path: String,
+    is_container: bool,
+}
+
+fn fill_json_tree(
+    tree: &mut views::SelectView<JsonTreeItem>,
+    value: &serde_json::Value,
+    collapsed: &HashSet<String>,
+    selected_path: Option<&str>,
+) {
+    tree.clear();
+
+    for line in json_lines(value, collapsed) {
+        tree.add_item(
+            line.text,
+            JsonTreeItem {
+                path: line.path,
+                is_container: line.is_container,
+            },
+        );
+    }
+
+    if let Some(idx) =
+        selected_path.and_then(|path| tree.iter().position(|(_, item)| item.path == path))
+    {
+        tree.set_selection(idx);
+    }
+}
+
+fn build_json_path_prompt(value: serde_json::Value) -> impl cursive::view::View {
+    let input = views::EditView::new().on_submit(move |siv, path| {
+        let text = match value.query(path) {
+            Ok(results) => {
+                serde_json::to_string_pretty(&results).unwrap_or_else(|err| err.to_string())
+            }
+            Err(err) => format!("Error evaluating JSONPath: {err}"),
+        };
+        siv.call_on_name("json_path_results", |v: &mut views::TextView| {
+            v.set_content(text)
+        });
+    });
+
+    views::OnEventView::new(
+        views::LinearLayout::vertical()
+            .child(views::Panel::new(input).title("JSONPath"))
+            .child(views::ScrollView::new(
+                views::TextView::new("").with_name("json_path_results"),
+            ))
+            .child(views::Button::new("Close", |s| {
+                s.pop_layer();
+            })),
+    )
+    .on_event(cursive::event::Key::Esc, |siv| {
+        siv.pop_layer();
+    })
+}
+
+fn on_copy_json_path(siv: &mut cursive::Cursive, column: &str, path: &str) {
+    let snippet = json_path_snippet(column, path);
+    let message = match copy_to_clipboard(&snippet) {
+        Ok(()) => format!("Copied to the clipboard:\n\n{snippet}"),
+        Err(err) => format!("Error copying to the clipboard ({err}), snippet:\n\n{snippet}"),
+    };
+    siv.add_layer(views::Dialog::info(message));
+}
+
+// Each mounted row route gets its own relations view name, so that a background
+// count query finishing late never updates the view of another route.
+static NEXT_RELATIONS_VIEW_ID: AtomicUsize = AtomicUsize::new(0);
+
+const RELATION_COUNT_LIMIT: usize = 1000;
+
+enum RelationCount {
+    Pending,
+    Count(usize),
+    Prompted,
+    Error,
+}
+
+struct RowRoute {
+    resource_id: String,
+    row: IndexedRow,
+    relation_counts: Arc<Mutex<HashMap<String, RelationCount>>>,
+    // Set when the route is unmounted, to stop the counts of its last mount
+    stop_counting: Mutex<Arc<AtomicBool>>,
+}
+
+impl Route for RowRoute {
+    fn mount(&self, app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, router: &Router) {
+        let relations_view_name = format!(
+            "relations_{}",
+            NEXT_RELATIONS_VIEW_ID.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let formatter = ValueFormatter::new(&app_data_ptr, &self.resource_id);
+        let title = match formatter.title(&self.row.row) {
+            Some(row_title) => format!("{}: {row_title}", &formatter.resource_name),
+            None => formatter.resource_name.clone(),
+        };
+
+        let relations = build_relations(
+            Arc::clone(&app_data_ptr),
+            siv,
+            router,
+            self,
+            &relations_view_name,
+        );
+
+        let layout = views::LinearLayout::horizontal()
+            .child(build_row_view(&self.row.row, &formatter, router).full_width())
+            .child(views::Panel::new(relations).title("Relations"));
+
+        let view = {
+            let router = router.clone();
+            let resource_id = self.resource_id.clone();
+            let row = self.row.clone();
+            views::OnEventView::new(views::LinearLayout::vertical().child(layout).child(
+                views::Button::new("Close", {
+                    let router = router.clone();
+                    move |siv| router.pop(siv)
+                }),
+            ))
+            .on_event('l', {
+                let router = router.clone();
+                move |siv| {
+                    on_show_links(
+                        Arc::clone(&app_data_ptr),
+                        siv,
+                        &router,
+                        &resource_id,
+                        vec![row.clone()],
+                    )
+                }
+            })
+            .on_event(cursive::event::Key::Esc, move |siv| {
+                router.pop(siv);
+            })
+        };
+
+        add_route_layer(siv, views::Dialog::around(view).title(title));
+    }
+
+    fn unmount(&self, _app_data_ptr: AppDataPtr, siv: &mut cursive::Cursive, _router: &Router) {
+        self.stop_counting
+            .lock()
+            .unwrap()
+            .store(true, Ordering::Relaxed);
+        pop_route_layer(siv);
+    }
+
+    fn is_valid(&self, resources: &HashMap<String, Resource>) -> bool {
+        resources.contains_key(&self.resource_id)
+    }
+
+    fn actions(
+        &self,
+        app_data_ptr: &AppDataPtr,
+        _siv: &mut cursive::Cursive,
+        router: &Router,
+    ) -> Vec<Action> {
+        // Run directly, the focus can be in the relations
+        let show_links = {
+            let app_data_ptr = Arc::clone(app_data_ptr);
+            let router = router.clone();
+            let resource_id = self.resource_id.clone();
+            let row = self.row.clone();
+            Action::new("Show the links of the row", move |siv| {
+                on_show_links(
+                    Arc::clone(&app_data_ptr),
+                    siv,
+                    &router,
+                    &resource_id,
+                    vec![row.clone()],
+                )
+            })
+            .with_key("l")
+        };
+
+        let mut actions = vec![show_links];
+        actions.extend(link_actions(
+            app_data_ptr,
+            router,
+            &self.resource_id,
+            vec![self.row.clone()],
+        ));
+        actions
+    }
+}
+
+fn relation_label(link_name: &str, count: Option<&RelationCount>) -> String {
+    match count {
+        None | Some(RelationCount::Pending) => format!("{link_name} (…)"),
+        Some(RelationCount::Count(count)) if *count > RELATION_COUNT_LIMIT => {
+            format!("{link_name} ({RELATION_COUNT_LIMIT}+)")
+        }
+        Some(RelationCount::Count(count)) => format!("{link_name} ({count})"),
+        Some(RelationCount::Prompted) => format!("{link_name} (?)"),
+        Some(RelationCount::Error) => format!("{link_name} (!)"),
+    }
+}
+
+fn update_relation_labels(
+    v: &mut views::SelectView,
+    relation_counts: &HashMap<String, RelationCount>,
+    pinned: Option<&[Option<char>]>,
+) {
+    for (label, link_name) in v.iter_mut() {
+        *label = relation_label(link_name, relation_counts.get(link_name.as_str())).into();
+    }
+    if let Some(pinned) = pinned {
+        highlight_shortcuts(v, pinned);
+    }
+}
+
+fn build_relations(
+    app_data_ptr: AppDataPtr,
+    siv: &mut cursive::Cursive,
+    router: &Router,
+    route: &RowRoute,
+    view_name: &str,
+) -> impl cursive::view::View {
+    let resource_id = route.resource_id.as_str();
+    let row = &route.row;
+    let relation_counts = Arc::clone(&route.relation_counts);
+    let stop_counting = Arc::new(AtomicBool::new(false));
+    *route.stop_counting.lock().unwrap() = Arc::clone(&stop_counting);
+    let r = get_resource(&app_data_ptr, resource_id);
+    let (link_names, pinned): (Vec<String>, Vec<Option<char>>) = sorted_links(&r)
+        .into_iter()
+        .filter(|(link_name, link)| link_condition_matches(link_name, link, &row.row))
+        .map(|(link_name, link)| (link_name.clone(), link.shortcut))
+        .unzip();
+    let descriptions: HashMap<String, String> = link_names
+        .iter()
+        .filter_map(|link_name| Some((link_name.clone(), r.links[link_name].description.clone()?)))
+        .collect();
+    let details = link_details(&app_data_ptr, resource_id);
+
+    let mut to_count: Vec<String> = Vec::new();
+    {
+        let mut counts = relation_counts.lock().unwrap();
+        for link_name in &link_names {
+            if counts.contains_key(link_name) {
+                continue;
+            }
+
+            let link = r.links.get(link_name).expect("invalid link name");
+            if link.search_params.iter().any(ColumnExpression::is_prompt) {
+                counts.insert(link_name.clone(), RelationCount::Prompted);
+            } else {
+                counts.insert(link_name.clone(), RelationCount::Pending);
+                to_count.push(link_name.clone());
+            }
+        }
+    }
+
+    let mut select_view = views::SelectView::new();
+    {
+        let counts = relation_counts.lock().unwrap();
+        for link_name in &link_names {
+            select_view.add_item(
+                relation_label(link_name, counts.get(link_name)),
+                link_name.clone(),
+            );
+        }
+    }
+
+    {
+        let app_data_ptr = Arc::clone(&app_data_ptr);
+        let resource_id = resource_id.to_owned();
+        let row = row.clone();
+        let router = router.clone();
+        select_view.set_on_submit(move |siv, link_name: &String| {
+            on_pick_link(
+                Arc::clone(&app_data_ptr),
+                siv,
+                &router,
+                &resource_id,
+                link_name,
+                std::slice::from_ref(&row),
+            )
+        });
+    }
+
+    let mode = app_data_ptr.lock().unwrap().settings.picker_mode;
+    let filter = uses_filter(mode, &select_view, &pinned);
+    if !to_count.is_empty() {
+        let cb_sink = siv.cb_sink().clone();
+        let resource_id = resource_id.to_owned();
+        let row = row.row.clone();
+        let view_name = view_name.to_owned();
+        let relation_counts = Arc::clone(&relation_counts);
+        let pinned = (!filter).then(|| pinned.clone());
+
+        // The queries lock AppData and the database in turn, never together,
+        // so the UI isn't blocked while they run
+        std::thread::spawn(move || {
+            for (idx, link_name) in to_count.iter().enumerate() {
+                if stop_counting.load(Ordering::Relaxed) {
+                    // Counted when the route is mounted again
+                    let mut counts = relation_counts.lock().unwrap();
+                    for link_name in &to_count[idx..] {
+                        counts.remove(link_name);
+                    }
+                    return;
+                }
+
+                let count = match count_link_rows(&app_data_ptr, &resource_id, link_name, &row) {
+                    Ok(count) => RelationCount::Count(count),
+                    Err(err) => {
+                        log::warn!("Error counting relation {link_name}: {err:#}");
+                        RelationCount::Error
+                    }
+                };
+                relation_counts
+                    .lock()
+                    .unwrap()
+                    .insert(link_name.clone(), count);
+
+                let relation_counts = Arc::clone(&relation_counts);
+                let view_name = view_name.clone();
+                let pinned = pinned.clone();
+                let sent = cb_sink.send(Box::new(move |siv| {
+                    siv.call_on_name(&view_name, |v: &mut views::SelectView| {
+                        update_relation_labels(
+                            v,
+                            &relation_counts.lock().unwrap(),
+                            pinned.as_deref(),
+                        )
+                    });
+                }));
+                if sent.is_err() {
+                    return; // the UI is gone
+                }
+            }
+        });
+    }
+
+    let labeler: Labeler = Arc::new(move |link_name: &str| {
+        relation_label(link_name, relation_counts.lock().unwrap().get(link_name))
+    });
+    build_described_picker(
+        select_view,
+        view_name,
+        pinned,
+        descriptions,
+        details,
+        mode,
+        Some(labeler),
+    )
+}
+
+fn on_show_links(
+    app_data_ptr: AppDataPtr,
+    siv: &mut cursive::Cursive,
+    router: &Router,
+    resource_id: &str,
+    rows: Vec<IndexedRow>,
+) {
+    siv.add_layer(views::Dialog::around(
+        views::OnEventView::new(build_link_picker(
+            Arc::clone(&app_data_ptr),
             router,
             resource_id,
-            row,
+            rows,
         ))
         .on_event(cursive::event::Key::Esc, |siv| {
             siv.pop_layer();
@@ -681,52 +3218,174 @@ fn evaluate_link_condition(cond: Option<LinkCondition>, row: &ResultRow) -> Resu
                 .with_context(|| format!("dereferenced value {:?} is not a string", results[0]))?;
             val_str == expected
         }
+        LinkCondition::Eq(ColumnExpression::Template { template }, expected) => {
+            render_template(&template, |col| column_value_as_string(row, col))? == expected
+        }
+        LinkCondition::Eq(ColumnExpression::Value { value }, expected) => {
+            literal_as_param_string(&value) == expected
+        }
+        LinkCondition::Eq(ColumnExpression::Prompt { .. }, _) => {
+            bail!("link conditions can't use prompted values")
+        }
     };
     Ok(matches)
 }
 
+fn column_value_as_string(row: &ResultRow, col_name: &str) -> Result<String> {
+    let val: SQLValueAsString = row
+        .0
+        .try_get(col_name)
+        .with_context(|| format!("error decoding column {col_name} as string"))?;
+    Ok(val.take_string())
+}
+
+fn json_value_as_param_string(val: &serde_json::Value) -> String {
+    match val {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+fn literal_param_strings(val: &serde_json::Value) -> Vec<String> {
+    match val {
+        serde_json::Value::Array(items) => items.iter().map(json_value_as_param_string).collect(),
+        val => vec![json_value_as_param_string(val)],
+    }
+}
+
+fn literal_as_param_string(val: &serde_json::Value) -> String {
+    literal_param_strings(val).join(",")
+}
+
+fn link_condition_matches(link_name: &str, link: &Link, row: &ResultRow) -> bool {
+    evaluate_link_condition(link.condition.clone(), row).unwrap_or_else(|err| {
+        log::warn!("Error evaluating condition for link {link_name}: {err}");
+        true
+    })
+}
+
+fn link_details(
+    app_data_ptr: &AppDataPtr,
+    resource_id: &str,
+) -> impl Fn(&str) -> Option<(String, String)> + Send + Sync + use<> {
+    let app_data_ptr = Arc::clone(app_data_ptr);
+    let resource_id = resource_id.to_owned();
+    move |link_name| {
+        let app_data = app_data_ptr.lock().unwrap();
+        let link = app_data.resources.get(&resource_id)?.links.get(link_name)?;
+        let text = link_help(link, app_data.resources.get(&link.kind));
+        Some((format!("Link {link_name}"), text))
+    }
+}
+
+fn link_actions(
+    app_data_ptr: &AppDataPtr,
+    router: &Router,
+    resource_id: &str,
+    rows: Vec<IndexedRow>,
+) -> Vec<Action> {
+    let r = get_resource(app_data_ptr, resource_id);
+    let rows = Arc::new(rows);
+
+    sorted_links(&r)
+        .into_iter()
+        .filter(|(link_name, link)| {
+            rows.iter()
+                .any(|row| link_condition_matches(link_name, link, &row.row))
+        })
+        .map(|(link_name, _)| {
+            let app_data_ptr = Arc::clone(app_data_ptr);
+            let router = router.clone();
+            let resource_id = resource_id.to_owned();
+            let rows = Arc::clone(&rows);
+            let link_name = link_name.clone();
+            Action::new(format!("Follow link {link_name}"), move |siv| {
+                on_pick_link(
+                    Arc::clone(&app_data_ptr),
+                    siv,
+                    &router,
+                    &resource_id,
+                    &link_name,
+                    &rows,
+                )
+            })
+        })
+        .collect()
+}
+
 fn build_link_picker(
     app_data_ptr: AppDataPtr,
     router: &Router,
     resource_id: &str,
-    row: &ResultRow,
+    rows: Vec<IndexedRow>,
 ) -> impl cursive::view::View {
     let mut select_view = views::SelectView::new();
 
     let r = get_resource(&app_data_ptr, resource_id);
 
-    for (link_name, link) in r.links {
-        if !evaluate_link_condition(link.condition, row).unwrap_or_else(|err| {
-            eprintln!("Error evaluating condition for link {link_name}: {err}");
-            true
-        }) {
+    let title = match rows.as_slice() {
+        [row] => match ValueFormatter::new(&app_data_ptr, resource_id).title(&row.row) {
+            Some(row_title) => format!("Links for {row_title}"),
+            None => "Links".to_owned(),
+        },
+        rows => format!("Links for {} rows", rows.len()),
+    };
+
+    let mut pinned = Vec::new();
+    let mut descriptions = HashMap::new();
+    for (link_name, link) in sorted_links(&r) {
+        if !rows
+            .iter()
+            .any(|row| link_condition_matches(link_name, link, &row.row))
+        {
             continue;
         }
 
         select_view.add_item_str(link_name);
+        pinned.push(link.shortcut);
+        if let Some(description) = &link.description {
+            descriptions.insert(link_name.clone(), description.clone());
+        }
     }
-
-    select_view.sort_by_label();
+    let details = link_details(&app_data_ptr, resource_id);
+    let mode = app_data_ptr.lock().unwrap().settings.picker_mode;
 
     {
         let resource_id = resource_id.to_owned();
-        let row = row.clone();
         let router = router.clone();
         select_view.set_on_submit(move |s, link_name| {
+            s.pop_layer(); // close the link picker
             on_pick_link(
                 Arc::clone(&app_data_ptr),
                 s,
                 &router,
                 &resource_id,
                 link_name,
-                &row,
+                &rows,
             )
         });
     }
 
     views::LinearLayout::vertical()
-        .child(views::TextView::new("Links"))
-        .child(build_shortcut_select_view(select_view, "link_picker"))
+        .child(views::TextView::new(title))
+        .child(build_described_picker(
+            select_view,
+            "link_picker",
+            pinned,
+            descriptions,
+            details,
+            mode,
+            None,
+        ))
+}
+
+struct LinkQuery {
+    kind: String,
+    title: String,
+    connection: String,
+    sql: String,
+    params: Vec<SqlValue>,
+    hidden_params: Vec<bool>,
 }
 
 fn on_pick_link_helper(
@@ -734,10 +3393,51 @@ fn on_pick_link_helper(
     resource_id: &str,
     link_name: &str,
     row: &ResultRow,
-) -> Result<(String, String, Vec<postgres::Row>)> {
-    let r = get_resource(&app_data_ptr, resource_id);
-    let links = r.links;
-    let link = links.get(link_name).expect("invalid link name");
+) -> Result<(String, String, Vec<postgres::Row>, QueryInfo)> {
+    let query = link_query(&app_data_ptr, resource_id, link_name, row)?;
+    let (rows, mut info) = run_query(&app_data_ptr, &query.connection, &query.sql, query.params)?;
+    info.hidden_params = query.hidden_params;
+
+    Ok((query.kind, query.title, rows, info))
+}
+
+fn count_link_rows(
+    app_data_ptr: &AppDataPtr,
+    resource_id: &str,
+    link_name: &str,
+    row: &ResultRow,
+) -> Result<usize> {
+    let query = link_query(app_data_ptr, resource_id, link_name, row)?;
+    let (rows, _, _) = execute_query(
+        app_data_ptr,
+        &query.connection,
+        &count_query(&query.sql),
+        &query.params,
+    )?;
+    let count: i64 = rows.first().context("no count returned")?.try_get(0)?;
+    let count = usize::try_from(count)?;
+    record_link_audit(app_data_ptr, resource_id, link_name, &[row], count, true)?;
+    Ok(count)
+}
+
+// The query is put on its own lines so that a trailing comment doesn't hide
+// the end of the count
+fn count_query(sql: &str) -> String {
+    let sql = sql.trim_end().trim_end_matches(';');
+    format!(
+        "SELECT count(*) FROM (SELECT 1 FROM (\n{sql}\n) AS q LIMIT {}) AS q",
+        RELATION_COUNT_LIMIT + 1
+    )
+}
+
+fn link_query(
+    app_data_ptr: &AppDataPtr,
+    resource_id: &str,
+    link_name: &str,
+    row: &ResultRow,
+) -> Result<LinkQuery> {
+    let r = get_resource(app_data_ptr, resource_id);
+    let link = r.links.get(link_name).expect("invalid link name");
     let link_target_resource = {
         let app_data = app_data_ptr.lock().unwrap();
         app_data
@@ -751,88 +3451,391 @@ fn on_pick_link_helper(
         .get(&link.search)
         .expect("invalid link search name");
 
-    let mut title = String::new();
-    let mut param_values: Vec<Box<dyn postgres::types::ToSql + Sync>> = Vec::new();
+    let mut param_values: Vec<SqlValue> = Vec::new();
+    let mut title_items = Vec::new();
+    let formatter = ValueFormatter::new(app_data_ptr, resource_id);
 
-    write!(&mut title, "{} (", &r.name)?;
+    for (param, target_param) in link.search_params.iter().zip(link_search.params.iter()) {
+        let (param_value, title_item) = bind_link_param(row, param, target_param)?;
+        param_values.push(param_value);
+        title_items.push(formatter.param_value(param, title_item));
+    }
 
-    for (idx, (param, target_param)) in link
+    Ok(LinkQuery {
+        kind: link.kind.clone(),
+        title: link_title(&r.name, formatter.title(row), &title_items, link_name),
+        connection: link_target_resource.connection_name().to_owned(),
+        sql: link_search.query.clone(),
+        params: param_values,
+        hidden_params: link
+            .search_params
+            .iter()
+            .map(|param| formatter.hides_param(param))
+            .collect(),
+    })
+}
+
+// The row is identified by its title, else by the values of the parameters
+fn link_title(
+    resource_name: &str,
+    row_title: Option<String>,
+    title_items: &[String],
+    link_name: &str,
+) -> String {
+    match row_title {
+        Some(row_title) => format!("{resource_name}: {row_title} → {link_name}"),
+        None => format!("{resource_name} ({}) → {link_name}", title_items.join(", ")),
+    }
+}
+
+fn on_pick_link_batch_helper(
+    app_data_ptr: AppDataPtr,
+    resource_id: &str,
+    link_name: &str,
+    rows: &[IndexedRow],
+) -> Result<QueryResultsRoute> {
+    let r = get_resource(&app_data_ptr, resource_id);
+    let link = r.links.get(link_name).expect("invalid link name");
+    let formatter = ValueFormatter::new(&app_data_ptr, resource_id);
+
+    if link.search_params.iter().any(ColumnExpression::is_prompt) {
+        bail!("links with prompted parameters can only be followed for one row at a time");
+    }
+
+    let rows: Vec<&IndexedRow> = rows
+        .iter()
+        .filter(|row| link_condition_matches(link_name, link, &row.row))
+        .collect();
+    let rows_values = rows
+        .iter()
+        .map(|row| {
+            link.search_params
+                .iter()
+                .map(|param| link_param_values(&row.row, param))
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()
+        .context("error binding search parameters")?;
+    let sources: Vec<String> = rows
+        .iter()
+        .zip(rows_values.iter())
+        .map(|(row, values)| link_source_label(&formatter, link, row, values))
+        .collect();
+
+    let title = format!("{} ({} rows) → {link_name}", &r.name, rows.len());
+
+    let Some(batch) = &link.batch else {
+        let mut results = Vec::new();
+        let mut results_sources = Vec::new();
+        let mut queries = Vec::new();
+
+        for (row, source) in rows.iter().zip(sources.into_iter()) {
+            let (_, _, row_results, info) =
+                on_pick_link_helper(Arc::clone(&app_data_ptr), resource_id, link_name, &row.row)
+                    .with_context(|| format!("error following link for row {source}"))?;
+            results_sources.extend(std::iter::repeat_n(source, row_results.len()));
+            results.extend(row_results);
+            queries.push(info);
+        }
+
+        let source_rows: Vec<&ResultRow> = rows.iter().map(|row| &row.row).collect();
+        record_link_audit(
+            &app_data_ptr,
+            resource_id,
+            link_name,
+            &source_rows,
+            results.len(),
+            false,
+        )?;
+
+        return Ok(QueryResultsRoute {
+            resource_id: link.kind.clone(),
+            title,
+            rows: results,
+            row_sources: results_sources,
+            queries,
+            selection: Default::default(),
+            show_hidden: Default::default(),
+        });
+    };
+
+    let target_resource = get_resource(&app_data_ptr, &link.kind);
+    let batch_search = target_resource
+        .search
+        .get(&batch.search)
+        .expect("invalid link batch search name");
+
+    let param_values = batch_param_values(&batch_search.params, &rows_values)?;
+
+    let (results, mut info) = run_query(
+        &app_data_ptr,
+        target_resource.connection_name(),
+        &batch_search.query,
+        param_values,
+    )?;
+    info.hidden_params = link
         .search_params
         .iter()
-        .zip(link_search.params.iter())
+        .map(|param| formatter.hides_param(param))
+        .collect();
+    let match_columns = batch
+        .match_column
+        .as_ref()
+        .map(MatchColumns::columns)
+        .unwrap_or_default();
+    let results_sources = results
+        .iter()
+        .map(|result| {
+            let matched: Option<Vec<(usize, ParamValue)>> = match_columns
+                .iter()
+                .map(|&(idx, col)| Some((idx, result.try_get(col).ok()?)))
+                .collect();
+            matched
+                .map(|matched| batch_result_sources(&sources, &rows_values, &matched))
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let source_rows: Vec<&ResultRow> = rows.iter().map(|row| &row.row).collect();
+    record_link_audit(
+        &app_data_ptr,
+        resource_id,
+        link_name,
+        &source_rows,
+        results.len(),
+        false,
+    )?;
+
+    Ok(QueryResultsRoute {
+        resource_id: link.kind.clone(),
+        title,
+        rows: results,
+        row_sources: results_sources,
+        queries: vec![info],
+        selection: Default::default(),
+        show_hidden: Default::default(),
+    })
+}
+
+fn link_source_label(
+    formatter: &ValueFormatter,
+    link: &Link,
+    row: &IndexedRow,
+    values: &[Vec<ParamValue>],
+) -> String {
+    if let Some(row_title) = formatter.title(&row.row) {
+        return format!("#{} {row_title}", row.idx);
+    }
+
+    let values: Vec<String> = link
+        .search_params
+        .iter()
+        .zip(values.iter())
+        .map(|(param, v)| {
+            let v: Vec<String> = v.iter().map(ParamValue::to_string).collect();
+            formatter.param_value(param, v.join(","))
+        })
+        .collect();
+    format!("#{} ({})", row.idx, values.join(", "))
+}
+
+fn batch_param_values(
+    params: &[SearchParam],
+    rows_values: &[Vec<Vec<ParamValue>>],
+) -> Result<Vec<SqlValue>> {
+    params
+        .iter()
         .enumerate()
-    {
-        let (param_value, title_item) = match param {
-            ColumnExpression::Name(name) => {
-                let col = row
-                    .0
-                    .columns()
-                    .iter()
-                    .find(|col| col.name() == name)
-                    .expect("invalid column name");
-                let col_ty = col.type_();
-
-                let val_title: SQLValueAsString = row
-                    .0
-                    .try_get(name.as_str())
-                    .unwrap_or_else(|err| SQLValueAsString::new(err.to_string()));
-
-                let val: Box<dyn postgres::types::ToSql + Sync> =
-                    if col_ty == &postgres::types::Type::TEXT {
-                        let val: Option<String> = row.0.get(name.as_str());
-                        Box::new(val)
-                    } else if col_ty == &postgres::types::Type::INT4 {
-                        let val: Option<i32> = row.0.get(name.as_str());
-                        Box::new(val)
-                    } else {
-                        todo!();
-                    };
-
-                (val, val_title.take_string())
-            }
-            ColumnExpression::JsonPath {
-                col_and_path: (col_name, path),
-            } => {
-                let col_value_title: SQLValueAsString = row
-                    .0
-                    .try_get(col_name.as_str())
-                    .unwrap_or_else(|err| SQLValueAsString::new(err.to_string()));
-                let col_value: serde_json::Value = row
-                    .0
-                    .try_get(col_name.as_str())
-                    .context("error parsing value as JSON")?;
-                let results = col_value.query(path).context("error dereferencing value")?;
-                let val = sql_value_from_json_slice(
-                    results.as_slice(),
-                    target_param.ty.clone().unwrap_or(SearchParamType::Text),
-                )?;
-
-                (val, format!("{path}={}", col_value_title.take_string()))
+        .map(|(idx, param)| {
+            let mut values: Vec<ParamValue> = Vec::new();
+            for value in rows_values.iter().flat_map(|row_values| &row_values[idx]) {
+                if !values.contains(value) {
+                    values.push(value.clone());
+                }
             }
-        };
+            sql_array_from_param_values(
+                &values,
+                param.ty.clone().unwrap_or(SearchParamType::TextArray),
+            )
+            .with_context(|| format!("error building array for parameter {}", param.name))
+        })
+        .collect()
+}
 
-        if idx > 0 {
-            write!(&mut title, ", ")?;
-        }
+// Labels of the rows a result of a batch search was found for: those having
+// the values of the matched columns of the result. Values are compared as
+// text, since the types of the parameters and the columns can differ.
+fn batch_result_sources(
+    sources: &[String],
+    rows_values: &[Vec<Vec<ParamValue>>],
+    matched: &[(usize, ParamValue)],
+) -> String {
+    if matched.is_empty() {
+        return String::new();
+    }
 
-        write!(&mut title, "{title_item}")?;
+    sources
+        .iter()
+        .zip(rows_values.iter())
+        .filter(|(_, row_values)| {
+            matched.iter().all(|(idx, value)| {
+                row_values
+                    .get(*idx)
+                    .is_some_and(|values| values.iter().any(|v| v.to_string() == value.to_string()))
+            })
+        })
+        .map(|(source, _)| source.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
 
-        param_values.push(param_value);
-    }
+fn bind_link_param(
+    row: &ResultRow,
+    param: &ColumnExpression,
+    target_param: &SearchParam,
+) -> Result<(SqlValue, String)> {
+    let ty = target_param.ty.clone().unwrap_or(SearchParamType::Text);
 
-    write!(&mut title, ") → {link_name}")?;
+    match param {
+        ColumnExpression::Name(name) => {
+            let col = row
+                .0
+                .columns()
+                .iter()
+                .find(|col| col.name() == name)
+                .with_context(|| format!("no column {name} in the row"))?;
+            // Without a declared type, bound with the type of the column,
+            // like the server expects when the search compares it to the
+            // same column
+            let col_ty = search_param_type(col.type_())
+                .filter(|ty| !ty.is_array())
+                .with_context(|| format!("column {name} of type {} can't be bound", col.type_()))?;
 
-    let param_values_ref: Vec<&(dyn postgres::types::ToSql + Sync)> =
-        param_values.iter().map(|v| v.as_ref()).collect();
+            let val_title: SQLValueAsString = row
+                .0
+                .try_get(name.as_str())
+                .unwrap_or_else(|err| SQLValueAsString::new(err.to_string()));
 
-    let mut app_data = app_data_ptr.lock().unwrap();
+            let val: ParamValue = row
+                .0
+                .try_get(name.as_str())
+                .with_context(|| format!("error decoding column {name}"))?;
+            let val = sql_value_from_param_value(&val, target_param.ty.clone().unwrap_or(col_ty))?;
 
-    let rows = app_data
-        .db
-        .query(&link_search.query, &param_values_ref)
-        .context("error running SQL query")?;
+            Ok((val, val_title.take_string()))
+        }
+        ColumnExpression::JsonPath {
+            col_and_path: (col_name, path),
+        } => {
+            let col_value_title: SQLValueAsString = row
+                .0
+                .try_get(col_name.as_str())
+                .unwrap_or_else(|err| SQLValueAsString::new(err.to_string()));
+            let col_value: serde_json::Value = row
+                .0
+                .try_get(col_name.as_str())
+                .context("error parsing value as JSON")?;
+            let results = col_value.query(path).context("error dereferencing value")?;
+            let val = sql_value_from_json_slice(results.as_slice(), ty)?;
+
+            Ok((val, format!("{path}={}", col_value_title.take_string())))
+        }
+        ColumnExpression::Template { template } => {
+            let rendered = render_template(template, |col| column_value_as_string(row, col))?;
+            let val = sql_value_from_string(&rendered, ty)?;
+
+            Ok((val, rendered))
+        }
+        ColumnExpression::Value { value } => {
+            let val = sql_value_from_literal(value, ty)?;
+
+            Ok((val, literal_as_param_string(value)))
+        }
+        ColumnExpression::Prompt { .. } => {
+            bail!("prompted values must be filled in through the search form")
+        }
+    }
+}
+
+fn link_param_values(row: &ResultRow, param: &ColumnExpression) -> Result<Vec<ParamValue>> {
+    match param {
+        ColumnExpression::Name(name) => {
+            Ok(vec![row.0.try_get(name.as_str()).with_context(|| {
+                format!("error decoding column {name}")
+            })?])
+        }
+        ColumnExpression::JsonPath {
+            col_and_path: (col_name, path),
+        } => {
+            let col_value: serde_json::Value = row
+                .0
+                .try_get(col_name.as_str())
+                .context("error parsing value as JSON")?;
+            let results = col_value.query(path).context("error dereferencing value")?;
+            Ok(results.into_iter().map(ParamValue::from).collect())
+        }
+        ColumnExpression::Template { template } => {
+            Ok(vec![ParamValue::Text(render_template(template, |col| {
+                column_value_as_string(row, col)
+            })?)])
+        }
+        ColumnExpression::Value { value } => Ok(match value {
+            serde_json::Value::Array(items) => items.iter().map(ParamValue::from).collect(),
+            value => vec![ParamValue::from(value)],
+        }),
+        ColumnExpression::Prompt { .. } => {
+            bail!("prompted values must be filled in through the search form")
+        }
+    }
+}
+
+fn link_param_strings(row: &ResultRow, param: &ColumnExpression) -> Result<Vec<String>> {
+    match param {
+        ColumnExpression::Name(name) => Ok(vec![column_value_as_string(row, name)?]),
+        ColumnExpression::JsonPath {
+            col_and_path: (col_name, path),
+        } => {
+            let col_value: serde_json::Value = row
+                .0
+                .try_get(col_name.as_str())
+                .context("error parsing value as JSON")?;
+            let results = col_value.query(path).context("error dereferencing value")?;
+            Ok(results
+                .into_iter()
+                .map(json_value_as_param_string)
+                .collect())
+        }
+        ColumnExpression::Template { template } => Ok(vec![render_template(template, |col| {
+            column_value_as_string(row, col)
+        })?]),
+        ColumnExpression::Value { value } => Ok(literal_param_strings(value)),
+        ColumnExpression::Prompt { default, .. } => Ok(default.iter().cloned().collect()),
+    }
+}
 
-    Ok((link.kind.clone(), title, rows))
+// Initial values of the form of a link with prompted parameters, and whether
+// they are bound from redacted columns, to hide them in the form
+fn link_form_values(
+    link: &Link,
+    formatter: &ValueFormatter,
+    param_strings: impl Fn(&ColumnExpression) -> Result<Vec<String>>,
+) -> Result<(Vec<String>, Vec<bool>)> {
+    let values = link
+        .search_params
+        .iter()
+        .enumerate()
+        .map(|(idx, param)| {
+            param_strings(param)
+                .map(|values| values.join(","))
+                .with_context(|| format!("error binding search parameter {idx}"))
+        })
+        .collect::<Result<_>>()?;
+    let hidden = link
+        .search_params
+        .iter()
+        .map(|param| formatter.hides_param(param))
+        .collect();
+    Ok((values, hidden))
 }
 
 fn on_pick_link(
@@ -841,20 +3844,70 @@ fn on_pick_link(
     router: &Router,
     resource_id: &str,
     link_name: &str,
-    row: &ResultRow,
+    rows: &[IndexedRow],
 ) {
-    siv.pop_layer(); // close the link picker
-    match on_pick_link_helper(Arc::clone(&app_data_ptr), resource_id, link_name, row) {
-        Ok((target_resource_id, title, rows)) => router.push(
+    let [row] = rows else {
+        match on_pick_link_batch_helper(Arc::clone(&app_data_ptr), resource_id, link_name, rows) {
+            Ok(results) => router.push(siv, Box::new(results)),
+            Err(err) => {
+                log::error!("Error running link query: {err:#}");
+                siv.add_layer(views::Dialog::around(build_query_error(&err)));
+            }
+        };
+        return;
+    };
+    let row = &row.row;
+
+    let r = get_resource(&app_data_ptr, resource_id);
+    let link = r.links.get(link_name).expect("invalid link name");
+
+    if link.search_params.iter().any(ColumnExpression::is_prompt) {
+        let formatter = ValueFormatter::new(&app_data_ptr, resource_id);
+        match link_form_values(link, &formatter, |param| link_param_strings(row, param)) {
+            Ok((initial_values, hidden)) => router.push(
+                siv,
+                Box::new(QueryRoute {
+                    resource_id: link.kind.clone(),
+                    search_id: link.search.clone(),
+                    initial_values,
+                    hidden,
+                }),
+            ),
+            Err(err) => {
+                log::error!("Error binding link parameters: {err:#}");
+                siv.add_layer(views::Dialog::around(build_query_error(&err)));
+            }
+        }
+        return;
+    }
+
+    let results = on_pick_link_helper(Arc::clone(&app_data_ptr), resource_id, link_name, row)
+        .and_then(|results| {
+            record_link_audit(
+                &app_data_ptr,
+                resource_id,
+                link_name,
+                &[row],
+                results.2.len(),
+                false,
+            )?;
+            Ok(results)
+        });
+    match results {
+        Ok((target_resource_id, title, rows, info)) => router.push(
             siv,
             Box::new(QueryResultsRoute {
                 resource_id: target_resource_id,
                 title,
                 rows,
+                row_sources: Vec::new(),
+                queries: vec![info],
+                selection: Default::default(),
+                show_hidden: Default::default(),
             }),
         ),
         Err(err) => {
-            eprintln!("Error running link query: {err:?}");
+            log::error!("Error running link query: {err:#}");
             siv.add_layer(views::Dialog::around(build_query_error(&err)));
         }
     };
@@ -874,18 +3927,430 @@ mod tests {
             "Slide",
             "Space",
             "User",
+            "Query",
+        ];
+        assert_eq!(
+            assign_shortcuts(items.iter().map(|item| (*item, None))),
+            vec![
+                Some((Some(0), 'c')),
+                Some((Some(5), 'l')),
+                Some((Some(10), 'i')),
+                Some((Some(0), 'p')),
+                Some((Some(0), 's')),
+                Some((Some(2), 'a')),
+                Some((Some(0), 'u')),
+                // q quits
+                Some((Some(3), 'r')),
+            ]
+        );
+
+        // Pinned keys are kept, even when added after other labels
+        let items = [
+            ("Case", None),
+            ("Space", None),
+            ("Slide", Some('s')),
+            ("User", Some('x')),
         ];
         assert_eq!(
             assign_shortcuts(items),
             vec![
-                Some((0, 'c')),
-                Some((5, 'l')),
-                Some((10, 'i')),
-                Some((0, 'p')),
-                Some((0, 's')),
-                Some((2, 'a')),
-                Some((0, 'u')),
+                Some((Some(0), 'c')),
+                Some((Some(1), 'p')),
+                Some((Some(0), 's')),
+                Some((None, 'x')),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_picker_order() {
+        let mut r: Resource = toml::from_str(
+            r#"
+            name = "User"
+
+            [links.Blogs]
+            kind = "blog"
+            search = "owner"
+            search_params = ["id"]
+
+            [links.Posts]
+            kind = "post"
+            search = "author"
+            search_params = ["id"]
+            shortcut = "b"
+
+            [links.Comments]
+            kind = "comment"
+            search = "author"
+            search_params = ["id"]
+            order = 2
+
+            [links.Quotes]
+            kind = "quote"
+            search = "author"
+            search_params = ["id"]
+            order = 1
+            "#,
+        )
+        .unwrap();
+        // Set when loading, in the order of definition
+        for (position, name) in ["Blogs", "Posts", "Comments", "Quotes"].iter().enumerate() {
+            r.links.get_mut(*name).unwrap().position = position;
+        }
+
+        // Links with an order come first, then the others as defined
+        let links = sorted_links(&r);
+        let names: Vec<&str> = links.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["Quotes", "Comments", "Blogs", "Posts"]);
+
+        // Pinned keys are kept whatever the order, the others skip them and the
+        // reserved keys
+        let shortcuts = assign_shortcuts(
+            links
+                .iter()
+                .map(|(name, link)| (name.as_str(), link.shortcut)),
+        );
+        assert_eq!(
+            shortcuts,
+            vec![
+                Some((Some(3), 't')),
+                Some((Some(0), 'c')),
+                Some((Some(1), 'l')),
+                Some((None, 'b')),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_palette_actions() {
+        let mut resources: HashMap<String, Resource> = toml::from_str(
+            r#"
+            [user]
+            name = "User"
+            [user.search.email]
+            query = "SELECT * FROM users WHERE email = $1"
+            [user.search.id]
+            query = "SELECT * FROM users WHERE id = $1"
+            order = 1
+
+            [blog]
+            name = "Blog"
+            order = 1
+            [blog.search.title]
+            query = "SELECT * FROM blogs WHERE title = $1"
+            "#,
+        )
+        .unwrap();
+        // Set when loading, in the order of definition
+        resources.get_mut("blog").unwrap().position = 1;
+        resources
+            .get_mut("user")
+            .unwrap()
+            .search
+            .get_mut("id")
+            .unwrap()
+            .position = 1;
+        let app_data_ptr = Arc::new(Mutex::new(AppData {
+            resources,
+            locations: HashMap::new(),
+            config_files: Vec::new(),
+            connections: HashMap::new(),
+            audit: None,
+            dbs: HashMap::new(),
+            connection_states: HashMap::new(),
+            connection_status: views::TextContent::new(""),
+            settings: Settings {
+                default_timezone: None,
+                statement_timeout: Duration::from_secs(1),
+                unmask: false,
+                picker_mode: PickerMode::Auto,
+            },
+        }));
+        let router = Router::new(app_data_ptr);
+
+        // Commands with a key show it, searches follow in the order of the pickers
+        let labels: Vec<String> = global_actions(&router, Path::new("dbdrill.toml"))
+            .iter()
+            .map(Action::palette_label)
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "Go back (Esc)",
+                "Open the SQL console (:)",
+                "Show the logs (~)",
+                "Reconnect to the databases (R)",
+                "Reload the configuration",
+                "Quit (q)",
+                "Search Blog by title",
+                "Search User by id",
+                "Search User by email",
             ]
         );
     }
+
+    #[test]
+    fn test_link_form_values() {
+        let link: Link = toml::from_str(
+            r#"
+            kind = "user"
+            search = "by_email"
+            search_params = ["email", "id", {prompt = true, default = "active"}]
+            "#,
+        )
+        .unwrap();
+        let mut formatter = ValueFormatter {
+            resource_name: "User".to_owned(),
+            title: None,
+            display: ResourceDisplay::default(),
+            redact: HashMap::from([("email".to_owned(), Redaction::Full)]),
+            default_timezone: None,
+        };
+        let param_strings = |param: &ColumnExpression| {
+            Ok(match param {
+                ColumnExpression::Name(name) => vec![format!("{name} value")],
+                ColumnExpression::Prompt { default, .. } => default.iter().cloned().collect(),
+                _ => Vec::new(),
+            })
+        };
+
+        // Values of redacted columns are still bound, but hidden
+        assert_eq!(
+            link_form_values(&link, &formatter, param_strings).unwrap(),
+            (
+                vec![
+                    "email value".to_owned(),
+                    "id value".to_owned(),
+                    "active".to_owned()
+                ],
+                vec![true, false, false]
+            )
+        );
+
+        // The form submits the real value instead of the mask in its field
+        let hidden_values = [Some("email value".to_owned()), None, None];
+        let fields = vec![MASK.to_owned(), "id value".to_owned(), "paid".to_owned()];
+        assert_eq!(
+            with_hidden_values(fields, &hidden_values),
+            vec!["email value", "id value", "paid"]
+        );
+
+        // Nothing is redacted with --unmask
+        formatter.redact.clear();
+        assert_eq!(
+            link_form_values(&link, &formatter, param_strings)
+                .unwrap()
+                .1,
+            vec![false, false, false]
+        );
+    }
+
+    #[test]
+    fn test_batch_link() {
+        let link: Link = toml::from_str(
+            r#"
+            kind = "blog"
+            search = "by_user"
+            search_params = ["id", "role"]
+            batch = {search = "by_users", match_column = ["user_id", "role"]}
+            "#,
+        )
+        .unwrap();
+        let batch = link.batch.unwrap();
+        assert_eq!(
+            batch.match_column.unwrap().columns(),
+            vec![(0, "user_id"), (1, "role")]
+        );
+        let batch: crate::model::LinkBatch = toml::from_str(
+            r#"search = "by_users"
+match_column = "user_id""#,
+        )
+        .unwrap();
+        assert_eq!(batch.match_column.unwrap().columns(), vec![(0, "user_id")]);
+
+        let text = |s: &str| ParamValue::Text(s.to_owned());
+        let rows_values = vec![
+            vec![vec![ParamValue::Int(1)], vec![text("editor")]],
+            vec![vec![ParamValue::Int(2)], vec![text("owner")]],
+            vec![vec![ParamValue::Int(1)], vec![text("owner")]],
+        ];
+
+        // Arrays of distinct values, untyped parameters bound as text
+        let search: Search = toml::from_str(
+            r#"
+            query = "SELECT * FROM user_blogs WHERE user_id = ANY($1) AND role = ANY($2)"
+            params = [{name = "User IDs", type = "integer[]"}, {name = "Roles"}]
+            "#,
+        )
+        .unwrap();
+        let values = batch_param_values(&search.params, &rows_values).unwrap();
+        assert_eq!(format!("{:?}", values[0]), "[Some(1), Some(2)]");
+        assert_eq!(
+            format!("{:?}", values[1]),
+            r#"[Some("editor"), Some("owner")]"#
+        );
+
+        // Results are labelled with the rows matching every matched parameter
+        let sources = ["#0".to_owned(), "#1".to_owned(), "#2".to_owned()];
+        assert_eq!(
+            batch_result_sources(
+                &sources,
+                &rows_values,
+                &[(0, ParamValue::Int(1)), (1, text("owner"))]
+            ),
+            "#2"
+        );
+        assert_eq!(
+            batch_result_sources(&sources, &rows_values, &[(0, ParamValue::Int(1))]),
+            "#0, #2"
+        );
+        assert_eq!(
+            batch_result_sources(&sources, &rows_values, &[(1, text("owner"))]),
+            "#1, #2"
+        );
+        // Without match columns, results are not labelled
+        assert_eq!(batch_result_sources(&sources, &rows_values, &[]), "");
+    }
+
+    #[test]
+    fn test_style_row() {
+        let display: ResourceDisplay = toml::from_str(
+            r#"
+            [[styles]]
+            if = {eq = ["status", "suspended"]}
+            style = "red"
+
+            [[styles]]
+            if = {eq = ["status", "suspended"]}
+            column = "status"
+            style = "bold"
+
+            [[styles]]
+            null = true
+            style = "dim"
+
+            [[styles]]
+            null = true
+            column = "email"
+            style = "italic"
+
+            [[styles]]
+            style = "not a style"
+            "#,
+        )
+        .unwrap();
+        let columns = ["id", "status", "email", "name"];
+        let style = |status: &str, nulls: &[usize]| {
+            style_row(
+                &display.styles,
+                &columns,
+                |condition| match condition {
+                    Some(LinkCondition::Eq(ColumnExpression::Name(col), expected)) => {
+                        Ok(col == "status" && status == expected)
+                    }
+                    Some(_) => bail!("unsupported condition"),
+                    None => Ok(true),
+                },
+                |idx| nulls.contains(&idx),
+            )
+        };
+        let parse = |style: &str| style.parse::<Style>().unwrap();
+
+        let res = style("active", &[]);
+        assert_eq!(res.row, None);
+        assert!(res.cells.is_empty());
+
+        // The row style is combined with the styles of the cells
+        let res = style("suspended", &[]);
+        assert_eq!(res.row, Some(parse("red")));
+        assert_eq!(res.column(0), Some(parse("red")));
+        assert_eq!(res.column(1), Some(parse("red").combine(parse("bold"))));
+
+        // NULL rules only style the NULL cells, of their column if they have one
+        let res = style("active", &[2, 3]);
+        assert_eq!(res.column(0), None);
+        assert_eq!(res.column(2), Some(parse("dim").combine(parse("italic"))));
+        assert_eq!(res.column(3), Some(parse("dim")));
+        let res = style("active", &[3]);
+        assert_eq!(res.column(2), None);
+    }
+
+    #[test]
+    fn test_titles() {
+        let mut formatter = ValueFormatter {
+            resource_name: "User".to_owned(),
+            title: Some("{name} <{email}>".to_owned()),
+            display: ResourceDisplay::default(),
+            redact: HashMap::from([("email".to_owned(), Redaction::Full)]),
+            default_timezone: None,
+        };
+        let values = HashMap::from([("name", "Bob"), ("email", "bob@example.com")]);
+        let column_value = |col: &str| {
+            values
+                .get(col)
+                .map(|value| value.to_string())
+                .with_context(|| format!("no column {col}"))
+        };
+
+        // Redacted columns are masked in titles too
+        let row_title = formatter.render_title(column_value);
+        assert_eq!(row_title.as_deref(), Some("Bob <********>"));
+        assert_eq!(
+            link_title("User", row_title, &["2".to_owned()], "Blogs"),
+            "User: Bob <********> → Blogs"
+        );
+
+        // Without a title, or when it can't be rendered, the parameters identify
+        // the row
+        formatter.title = Some("{name} ({missing})".to_owned());
+        let row_title = formatter.render_title(column_value);
+        assert_eq!(row_title, None);
+        assert_eq!(
+            link_title(
+                "User",
+                row_title,
+                &["2".to_owned(), MASK.to_owned()],
+                "Blogs"
+            ),
+            "User (2, ********) → Blogs"
+        );
+        formatter.title = None;
+        assert_eq!(formatter.render_title(column_value), None);
+    }
+
+    #[test]
+    fn test_count_query() {
+        assert_eq!(
+            count_query("SELECT * FROM posts WHERE author_id = $1; -- by author\n"),
+            "SELECT count(*) FROM (SELECT 1 FROM (\n\
+             SELECT * FROM posts WHERE author_id = $1; -- by author\n\
+             ) AS q LIMIT 1001) AS q"
+        );
+        assert_eq!(
+            count_query("SELECT * FROM posts ;\n"),
+            "SELECT count(*) FROM (SELECT 1 FROM (\nSELECT * FROM posts \n) AS q LIMIT 1001) AS q"
+        );
+
+        assert_eq!(
+            relation_label("Posts", Some(&RelationCount::Count(1000))),
+            "Posts (1000)"
+        );
+        assert_eq!(
+            relation_label("Posts", Some(&RelationCount::Count(1001))),
+            "Posts (1000+)"
+        );
+    }
+
+    #[test]
+    fn test_displayed_columns() {
+        let display = ResourceDisplay {
+            order: vec!["email".to_owned(), "missing".to_owned(), "id".to_owned()],
+            hidden: vec!["created_at".to_owned()],
+            ..Default::default()
+        };
+        let names = ["id", "created_at", "name", "email"];
+
+        assert_eq!(displayed_columns(names, &display, false), vec![3, 0, 2]);
+        assert_eq!(displayed_columns(names, &display, true), vec![3, 0, 1, 2]);
+    }
 }
@@ -0,0 +1,401 @@
+use std::ffi::{OsStr, OsString};
+use std::io::{BufRead, BufReader};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use postgres::config::Host;
+
+use crate::model::SshTunnel;
+use crate::pgconfig;
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const PORT_ATTEMPTS: usize = 3;
+// Delays before restarting ssh after it exited, doubled after each failure
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+pub struct Tunnel {
+    name: String,
+    child: Arc<Mutex<Child>>,
+    closed: Arc<AtomicBool>,
+}
+
+// Opens a tunnel to the database of a connection, and points the connection
+// to the local end of the tunnel
+pub fn open(name: &str, ssh: &SshTunnel, config: &mut postgres::Config) -> Result<Tunnel> {
+    open_with(OsStr::new("ssh"), name, ssh, config)
+}
+
+fn open_with(
+    program: &OsStr,
+    name: &str,
+    ssh: &SshTunnel,
+    config: &mut postgres::Config,
+) -> Result<Tunnel> {
+    // The host of the database is resolved by the bastion
+    let target_host = match config.get_hosts() {
+        [Host::Tcp(host)] => host.clone(),
+        [_] => bail!(
+            "SSH tunnels need the host of the database as seen from the bastion, not a Unix socket"
+        ),
+        _ => bail!("SSH tunnels need a single database host"),
+    };
+
+    // The free port found can be taken by another process before ssh listens
+    // on it, ssh then exits and another port is tried
+    let mut attempt = 1;
+    let (child, forward, local_port) = loop {
+        let local_port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .context("error finding a free local port")?
+            .port();
+        let forward = format!(
+            "{}:{local_port}:{target_host}:{}",
+            Ipv4Addr::LOCALHOST,
+            pgconfig::port(config)
+        );
+
+        match start(program, name, ssh, &forward, local_port) {
+            Ok(child) => break (child, forward, local_port),
+            Err(err)
+                if attempt < PORT_ATTEMPTS
+                    && TcpListener::bind((Ipv4Addr::LOCALHOST, local_port)).is_err() =>
+            {
+                log::warn!(
+                    "local port {local_port} of the SSH tunnel of {name} was taken: {err:#}"
+                );
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    };
+    log::info!("SSH tunnel of {name} listening on port {local_port}");
+
+    *config = pgconfig::with_address(config, &Ipv4Addr::LOCALHOST.to_string(), local_port);
+
+    let tunnel = Tunnel {
+        name: name.to_owned(),
+        child: Arc::new(Mutex::new(child)),
+        closed: Arc::new(AtomicBool::new(false)),
+    };
+    supervise(&tunnel, program.to_owned(), ssh.clone(), forward);
+    Ok(tunnel)
+}
+
+fn start(
+    program: &OsStr,
+    name: &str,
+    ssh: &SshTunnel,
+    forward: &str,
+    local_port: u16,
+) -> Result<Child> {
+    let (mut child, stderr) = spawn(program, name, ssh, forward)?;
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().context("error waiting for ssh")? {
+            let output = stderr.join().unwrap_or_default();
+            bail!("ssh to {} exited ({status}): {}", ssh.host, output.trim());
+        }
+        if TcpStream::connect((Ipv4Addr::LOCALHOST, local_port)).is_ok() {
+            return Ok(child);
+        }
+        if start.elapsed() > STARTUP_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("timed out waiting for ssh to {}", ssh.host);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// Starts ssh, with a thread logging its error output and returning it when ssh
+// exits
+fn spawn(
+    program: &OsStr,
+    name: &str,
+    ssh: &SshTunnel,
+    forward: &str,
+) -> Result<(Child, JoinHandle<String>)> {
+    let mut command = Command::new(program);
+    // Without a terminal to ask for passwords or to confirm host keys, ssh has
+    // to fail instead
+    command.args([
+        "-N",
+        "-o",
+        "BatchMode=yes",
+        "-o",
+        "ExitOnForwardFailure=yes",
+        "-L",
+        forward,
+    ]);
+    if let Some(port) = ssh.port {
+        command.arg("-p").arg(port.to_string());
+    }
+    if let Some(identity_file) = &ssh.identity_file {
+        command.arg("-i").arg(identity_file);
+    }
+    command
+        .arg("--")
+        .arg(&ssh.host)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    // Don't leave ssh running if dbdrill dies without closing the tunnel
+    #[cfg(target_os = "linux")]
+    unsafe {
+        use std::os::unix::process::CommandExt;
+
+        command.pre_exec(|| {
+            libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGTERM);
+            Ok(())
+        });
+    }
+
+    let mut child = command.spawn().context("error running ssh")?;
+    let stderr = child.stderr.take().expect("ssh stderr not piped");
+    let name = name.to_owned();
+    let output = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+            log::warn!("ssh ({name}): {line}");
+            output.push(line);
+        }
+        output.join("\n")
+    });
+
+    Ok((child, output))
+}
+
+// Restarts ssh when it exits, e.g. because the bastion was restarted. Lost
+// database connections are then reestablished through the new process.
+fn supervise(tunnel: &Tunnel, program: OsString, ssh: SshTunnel, forward: String) {
+    let name = tunnel.name.clone();
+    let child = Arc::clone(&tunnel.child);
+    let closed = Arc::clone(&tunnel.closed);
+
+    std::thread::spawn(move || {
+        let mut started = Instant::now();
+        let mut delay = MIN_RESTART_DELAY;
+
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let status = match child.lock().unwrap().try_wait() {
+                Ok(Some(status)) => status,
+                Ok(None) => continue,
+                Err(err) => {
+                    log::error!("Error waiting for the SSH tunnel of {name}: {err}");
+                    return;
+                }
+            };
+            if closed.load(Ordering::SeqCst) {
+                return;
+            }
+
+            if started.elapsed() > MAX_RESTART_DELAY {
+                delay = MIN_RESTART_DELAY;
+            }
+            log::error!("SSH tunnel of {name} exited ({status}), restarting it in {delay:?}");
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(MAX_RESTART_DELAY);
+
+            // Closing the tunnel takes the lock too, so that it never misses a
+            // new process
+            let mut child = child.lock().unwrap();
+            if closed.load(Ordering::SeqCst) {
+                return;
+            }
+            match spawn(&program, &name, &ssh, &forward) {
+                Ok((new_child, _)) => {
+                    *child = new_child;
+                    started = Instant::now();
+                }
+                Err(err) => log::error!("Error restarting the SSH tunnel of {name}: {err:#}"),
+            }
+        }
+    });
+}
+
+impl Drop for Tunnel {
+    fn drop(&mut self) {
+        let mut child = self.child.lock().unwrap();
+        self.closed.store(true, Ordering::SeqCst);
+
+        if let Err(err) = child.kill().and_then(|_| child.wait()) {
+            log::warn!("Error stopping the SSH tunnel of {}: {err}", self.name);
+        }
+        log::info!("SSH tunnel of {} closed", self.name);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    // Writes a fake ssh appending its arguments to <script>.args, one line per
+    // run numbered by $run. The tests play ssh's end of the tunnel by listening
+    // on the local port it was given.
+    fn fake_ssh(test: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("dbdrill-{test}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ssh");
+        let script =
+            format!("#!/bin/sh\necho \"$@\" >> \"$0.args\"\nrun=$(wc -l < \"$0.args\")\n{body}\n");
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn runs(script: &Path) -> Vec<String> {
+        std::fs::read_to_string(script.with_extension("args"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    // Arguments of a run of the fake ssh, waiting for it to start
+    fn wait_for_run(script: &Path, run: usize) -> String {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(10) {
+            if let Some(args) = runs(script).get(run - 1) {
+                return args.clone();
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("ssh run {run} didn't start");
+    }
+
+    fn local_port(args: &str) -> u16 {
+        let forward = args
+            .split(' ')
+            .skip_while(|arg| *arg != "-L")
+            .nth(1)
+            .unwrap();
+        forward.split(':').nth(1).unwrap().parse().unwrap()
+    }
+
+    // A socket bound to a port without listening on it, like a process that
+    // took the port but doesn't accept connections yet
+    fn take_port(port: u16) -> OwnedFd {
+        unsafe {
+            let fd = libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0);
+            assert!(fd >= 0);
+            let fd = OwnedFd::from_raw_fd(fd);
+
+            let mut addr: libc::sockaddr_in = std::mem::zeroed();
+            addr.sin_family = libc::AF_INET as libc::sa_family_t;
+            addr.sin_port = port.to_be();
+            addr.sin_addr.s_addr = u32::from(Ipv4Addr::LOCALHOST).to_be();
+            let res = libc::bind(
+                std::os::fd::AsRawFd::as_raw_fd(&fd),
+                (&raw const addr).cast(),
+                std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+            );
+            assert_eq!(res, 0);
+            fd
+        }
+    }
+
+    fn ssh() -> SshTunnel {
+        SshTunnel {
+            host: "bastion".to_owned(),
+            port: Some(2222),
+            identity_file: Some("id_bastion".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_open_and_restart() {
+        // The first run exits after a second, and is restarted
+        let script = fake_ssh(
+            "tunnel-restart",
+            "[ $run = 1 ] && sleep 1 && exit 1\nexec sleep 60",
+        );
+        let forwarder = {
+            let script = script.clone();
+            std::thread::spawn(move || {
+                let port = local_port(&wait_for_run(&script, 1));
+                TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap()
+            })
+        };
+
+        let mut config: postgres::Config = "postgres://alice@db.internal:6432/app".parse().unwrap();
+        let tunnel = open_with(script.as_os_str(), "test", &ssh(), &mut config).unwrap();
+        let _listener = forwarder.join().unwrap();
+
+        let port = pgconfig::port(&config);
+        assert_eq!(config.get_hosts(), [Host::Tcp("127.0.0.1".to_owned())]);
+        let args = format!(
+            "-N -o BatchMode=yes -o ExitOnForwardFailure=yes \
+             -L 127.0.0.1:{port}:db.internal:6432 -p 2222 -i id_bastion -- bastion"
+        );
+        assert_eq!(wait_for_run(&script, 1), args);
+        assert_eq!(wait_for_run(&script, 2), args);
+
+        drop(tunnel);
+        std::fs::remove_dir_all(script.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_retries_taken_port() {
+        // The first run fails to listen, once the test took its port
+        let script = fake_ssh(
+            "tunnel-port",
+            "[ $run = 1 ] && { while [ ! -e \"$0.taken\" ]; do sleep 0.05; done; exit 255; }\n\
+             exec sleep 60",
+        );
+        let forwarder = {
+            let script = script.clone();
+            std::thread::spawn(move || {
+                let taken = local_port(&wait_for_run(&script, 1));
+                let socket = take_port(taken);
+                std::fs::write(script.with_extension("taken"), "").unwrap();
+
+                let port = local_port(&wait_for_run(&script, 2));
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+                (taken, socket, listener)
+            })
+        };
+
+        let mut config: postgres::Config = "postgres://alice@db.internal/app".parse().unwrap();
+        let tunnel = open_with(script.as_os_str(), "test", &ssh(), &mut config).unwrap();
+        let (taken, _socket, _listener) = forwarder.join().unwrap();
+
+        assert_ne!(pgconfig::port(&config), taken);
+        assert_eq!(runs(&script).len(), 2);
+
+        drop(tunnel);
+        std::fs::remove_dir_all(script.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_open_error() {
+        let script = fake_ssh(
+            "tunnel-error",
+            "echo 'bastion: Permission denied (publickey).' >&2\nexit 255",
+        );
+
+        let mut config: postgres::Config = "postgres://alice@db.internal/app".parse().unwrap();
+        let err = open_with(script.as_os_str(), "test", &ssh(), &mut config)
+            .err()
+            .unwrap();
+        assert!(
+            format!("{err:#}").contains("Permission denied (publickey)"),
+            "{err:#}"
+        );
+        assert_eq!(runs(&script).len(), 1);
+
+        std::fs::remove_dir_all(script.parent().unwrap()).unwrap();
+    }
+}
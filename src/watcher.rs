@@ -0,0 +1,190 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+
+// Directories to watch to notice changes to the given files (or directories).
+// Editors often replace files instead of writing them, so watching the files
+// themselves would miss changes.
+fn watched_dirs(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = paths
+        .iter()
+        .map(|p| {
+            if p.is_dir() {
+                p.clone()
+            } else {
+                match p.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_owned(),
+                    _ => PathBuf::from("."),
+                }
+            }
+        })
+        .collect();
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+fn is_relevant(paths: &[PathBuf], name: &Path) -> bool {
+    name.extension().is_some_and(|ext| ext == "toml")
+        || paths
+            .iter()
+            .any(|p| p.file_name() == Some(name.as_os_str()))
+}
+
+// Blocks until one of the given files may have changed, or a TOML file was
+// added or removed next to them
+#[cfg(target_os = "linux")]
+pub fn wait_for_change(paths: &[PathBuf]) -> Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStringExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+
+    use anyhow::{Context, bail};
+
+    let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error()).context("error initializing inotify");
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    for dir in watched_dirs(paths) {
+        let c_dir = CString::new(dir.clone().into_os_string().into_vec())?;
+        let mask = libc::IN_CLOSE_WRITE
+            | libc::IN_MOVED_TO
+            | libc::IN_MOVED_FROM
+            | libc::IN_CREATE
+            | libc::IN_DELETE;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), c_dir.as_ptr(), mask) } < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("error watching {}", dir.display()));
+        }
+    }
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err).context("error reading inotify events");
+        }
+        if n == 0 {
+            bail!("inotify file descriptor closed");
+        }
+
+        if event_names(&buf[..n as usize])
+            .into_iter()
+            .any(|name| is_relevant(paths, Path::new(name)))
+        {
+            return Ok(());
+        }
+    }
+}
+
+// Names of the files of the events read from an inotify file descriptor. Each
+// event is an inotify_event struct followed by the name, padded with NUL bytes
+// to the length given by the struct.
+#[cfg(target_os = "linux")]
+fn event_names(buf: &[u8]) -> Vec<&std::ffi::OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+
+    const HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+    const LEN_OFFSET: usize = std::mem::offset_of!(libc::inotify_event, len);
+
+    let mut names = Vec::new();
+    let mut rest = buf;
+    while rest.len() >= HEADER_SIZE {
+        let len: [u8; 4] = rest[LEN_OFFSET..LEN_OFFSET + 4].try_into().unwrap();
+        let len = u32::from_ne_bytes(len) as usize;
+        let Some(name) = rest.get(HEADER_SIZE..HEADER_SIZE + len) else {
+            break;
+        };
+
+        let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+        names.push(std::ffi::OsStr::from_bytes(name));
+        rest = &rest[HEADER_SIZE + len..];
+    }
+
+    names
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wait_for_change(paths: &[PathBuf]) -> Result<()> {
+    let snapshot = || -> Vec<(PathBuf, Option<std::time::SystemTime>)> {
+        let mut entries: Vec<PathBuf> = watched_dirs(paths)
+            .iter()
+            .filter_map(|dir| std::fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .is_some_and(|n| is_relevant(paths, Path::new(n)))
+            })
+            .collect();
+        entries.sort();
+        entries
+            .into_iter()
+            .map(|p| {
+                let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok();
+                (p, modified)
+            })
+            .collect()
+    };
+
+    let initial = snapshot();
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        if snapshot() != initial {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_event_names() {
+        let event = |name: &str, len: u32| {
+            let mut buf = Vec::new();
+            for field in [1i32.to_ne_bytes(), libc::IN_CREATE.to_ne_bytes()] {
+                buf.extend(field);
+            }
+            buf.extend(0u32.to_ne_bytes()); // cookie
+            buf.extend(len.to_ne_bytes());
+            buf.extend(name.as_bytes());
+            buf.resize(buf.len() + len as usize - name.len(), 0);
+            buf
+        };
+
+        let mut buf = [event("dbdrill.toml", 16), event("x.swp", 8)].concat();
+        assert_eq!(event_names(&buf), ["dbdrill.toml", "x.swp"]);
+        // An event cut by the end of the buffer is skipped
+        buf.truncate(buf.len() - 2);
+        assert_eq!(event_names(&buf), ["dbdrill.toml"]);
+    }
+
+    #[test]
+    fn test_wait_for_change() {
+        let dir = std::env::temp_dir().join(format!("dbdrill-watcher-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dbdrill.toml");
+        std::fs::write(&path, "").unwrap();
+
+        let writer = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(1500));
+                std::fs::write(dir.join("other.txt"), "").unwrap();
+                std::fs::write(dir.join("more.toml"), "").unwrap();
+            })
+        };
+        wait_for_change(&[path]).unwrap();
+        writer.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}